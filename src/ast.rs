@@ -7,7 +7,7 @@ use std::convert::TryInto;
 
 pub type Var = String;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AssertionChain(pub Vec<Bexp>);
 
 impl AssertionChain {
@@ -28,7 +28,7 @@ impl AssertionChain {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct AxBlock(pub AssertionChain, pub Vec<(AxStm, AssertionChain)>);
 
 impl AxBlock {
@@ -47,6 +47,43 @@ impl AxBlock {
 
     }
 
+    pub fn modified_vars(&self) -> HashSet<Var> {
+        self.1.iter().flat_map(|(stm, _)| stm.modified_vars()).collect()
+    }
+
+    /// The integer variables occurring in the statements, all other variables of the assertions are logical variables
+    pub fn program_vars(&self) -> HashSet<Var> {
+        self.1.iter().flat_map(|(stm, _)| stm.program_vars()).collect()
+    }
+
+    /// The function and predicate applications in the assertions and statements, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        let mut calls: Vec<_> = (self.0).0.iter().flat_map(|assertion| assertion.calls()).collect();
+        for (stm, chain) in &self.1 {
+            calls.extend(stm.calls());
+            calls.extend(chain.0.iter().flat_map(|assertion| assertion.calls()));
+        }
+        calls
+    }
+
+    /// The first assertion of the block
+    pub fn pre(&self) -> &Bexp {
+        &(self.0).0[0]
+    }
+
+    /// The last assertion of the block
+    pub fn post(&self) -> &Bexp {
+        match self.1.last() {
+            Some((_, chain)) => chain.0.last().unwrap(),
+            None => (self.0).0.last().unwrap(),
+        }
+    }
+
+    /// The precondition, the program and the postcondition of the block, without the intermediate assertions
+    pub fn spec(&self) -> (Bexp, Box<Stm>, Bexp) {
+        (self.pre().clone(), self.clone().into_stm(), self.post().clone())
+    }
+
     pub fn into_stm(self) -> Box<Stm> {
         let AxBlock(_, rem) = self;
         let mut rem = rem.into_iter();
@@ -67,12 +104,18 @@ impl Debug for AxBlock {
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AxStm {
     Assign(Var, Aexp),
+    ArrAssign(Var, Aexp, Aexp),
     Skip,
     If(Bexp, AxBlock, AxBlock),
     While(Bexp, AxBlock),
+    Assert(Bexp),
+    Assume(Bexp),
+    Abort,
+    Repeat(AxBlock, Bexp),
+    For(Var, Aexp, Aexp, AxBlock),
 }
 
 impl AxStm {
@@ -80,6 +123,7 @@ impl AxStm {
         Box::new(match self {
             AxStm::Skip => Stm::Skip,
             AxStm::Assign(v, e) => Stm::Assign(v, Box::new(e)),
+            AxStm::ArrAssign(a, i, e) => Stm::ArrAssign(a, Box::new(i), Box::new(e)),
             AxStm::If(cond, then_block, else_block) => {
                 let then_stm = then_block.into_stm();
                 let else_stm = else_block.into_stm();
@@ -88,44 +132,170 @@ impl AxStm {
             AxStm::While(cond, inner_block) => {
                 let inner_stm = inner_block.into_stm();
                 Stm::While(Box::new(cond), inner_stm)
-            }
+            },
+            AxStm::Assert(cond) => Stm::Assert(Box::new(cond)),
+            AxStm::Assume(cond) => Stm::Assume(Box::new(cond)),
+            AxStm::Abort => Stm::Abort,
+            AxStm::Repeat(inner_block, cond) => Stm::Repeat(inner_block.into_stm(), Box::new(cond)),
+            AxStm::For(v, from, to, inner_block) => Stm::For(v, Box::new(from), Box::new(to), inner_block.into_stm()),
         })
     }
 
+    /// For a `while` or `repeat` loop in a total correctness proof, splits the inner pre-condition
+    /// `partial and variant = LOGICAL_VAR` into its parts
     pub fn get_while_things(&self) -> (&Box<Bexp>, &Box<Aexp>, &Box<Aexp>) {
-        match &self {
-            AxStm::While(cond, AxBlock(AssertionChain(inner_pre_chain), inner_rem)) => {
-                let inner_pre = inner_pre_chain.first().unwrap();
-                let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
-
-                match inner_pre {
-                    Bexp::Bop(partial, Bopcode::And, variant_exp) => {
-                        match variant_exp.as_ref() {
-                            Bexp::Rop(variant, Ropcode::Eq, logical_var) => (partial, variant, logical_var),
-                            _ => panic!("A total correctness proof requires an inner pre-condition of the form { condition and ( P ) and variant = LOGICAL_VAR}"),
-                        }
-                    },
-                    _ => panic!("A total correctness proof requires an inner pre-condition of the form { condition and ( P ) and variant = LOGICAL_VAR}"),
-                }
+        if let Bexp::Bop(partial, Bopcode::And, variant_exp) = self.inner_pre() {
+            if let Bexp::Rop(variant, Ropcode::Eq, logical_var) = variant_exp.as_ref() {
+                return (partial, variant, logical_var);
+            }
+        }
+
+        panic!(
+            "A total correctness proof requires an inner pre-condition of the form {{ {} }}",
+            if let AxStm::While(..) = self { "condition and ( P ) and variant = LOGICAL_VAR" } else { "P and variant = LOGICAL_VAR" }
+        )
+    }
+
+    /// Like `get_while_things`, but an inner pre-condition of the wrong form is an `AxStructureError`, whose expected
+    /// pre-condition is the actual one with `variant = LOGICAL_VAR` appended
+    pub fn try_get_while_things(&self) -> crate::Result<(&Bexp, &Aexp, &Aexp)> {
+        let inner_pre = self.inner_pre();
+        if let Bexp::Bop(partial, Bopcode::And, variant_exp) = inner_pre {
+            if let Bexp::Rop(variant, Ropcode::Eq, logical_var) = variant_exp.as_ref() {
+                return Ok((partial, variant, logical_var));
             }
+        }
+
+        let variant_exp = Bexp::Rop(
+            Box::new(Aexp::Var("variant".to_owned())), Ropcode::Eq, Box::new(Aexp::Var("LOGICAL_VAR".to_owned()))
+        );
+        Err(crate::error::ImpErrorInner::AxStructureError {
+            actual: inner_pre.clone(),
+            expected: Bexp::Bop(Box::new(inner_pre.clone()), Bopcode::And, Box::new(variant_exp)),
+            stm: self.clone(),
+        }.into())
+    }
+
+    /// The first assertion of the body of a `while` or `repeat` loop
+    fn inner_pre(&self) -> &Bexp {
+        match &self {
+            AxStm::While(_, inner_block) | AxStm::Repeat(inner_block, _) => inner_block.pre(),
             _ => unreachable!()
         }
     }
 
+    pub fn modified_vars(&self) -> HashSet<Var> {
+        match self {
+            AxStm::Assign(v, _) | AxStm::ArrAssign(v, _, _) => HashSet::from([v.clone()]),
+            AxStm::If(_, then_block, else_block) => {
+                let mut vars = then_block.modified_vars();
+                vars.extend(else_block.modified_vars());
+                vars
+            },
+            AxStm::While(_, inner_block) | AxStm::Repeat(inner_block, _) => inner_block.modified_vars(),
+            AxStm::For(v, _, _, inner_block) => {
+                let mut vars = inner_block.modified_vars();
+                vars.insert(v.clone());
+                vars
+            },
+            _ => HashSet::new(),
+        }
+    }
+
+    pub fn program_vars(&self) -> HashSet<Var> {
+        match self {
+            AxStm::Assign(v, aexp) => {
+                let mut vars = aexp.free_vars();
+                vars.insert(v.clone());
+                vars
+            },
+            AxStm::ArrAssign(_, index, aexp) => {
+                let mut vars = index.free_vars();
+                vars.extend(aexp.free_vars());
+                vars
+            },
+            AxStm::If(cond, then_block, else_block) => {
+                let mut vars = cond.free_vars();
+                vars.extend(then_block.program_vars());
+                vars.extend(else_block.program_vars());
+                vars
+            },
+            AxStm::While(cond, inner_block) | AxStm::Repeat(inner_block, cond) => {
+                let mut vars = cond.free_vars();
+                vars.extend(inner_block.program_vars());
+                vars
+            },
+            AxStm::For(v, from, to, inner_block) => {
+                let mut vars = inner_block.program_vars();
+                vars.extend(from.free_vars());
+                vars.extend(to.free_vars());
+                vars.insert(v.clone());
+                vars
+            },
+            AxStm::Assert(cond) | AxStm::Assume(cond) => cond.free_vars(),
+            AxStm::Skip | AxStm::Abort => HashSet::new(),
+        }
+    }
+
+    /// The function and predicate applications in the statement and its blocks, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match self {
+            AxStm::Assign(_, aexp) => aexp.calls(),
+            AxStm::ArrAssign(_, index, aexp) => {
+                let mut calls = index.calls();
+                calls.extend(aexp.calls());
+                calls
+            },
+            AxStm::If(cond, then_block, else_block) => {
+                let mut calls = cond.calls();
+                calls.extend(then_block.calls());
+                calls.extend(else_block.calls());
+                calls
+            },
+            AxStm::While(cond, inner_block) | AxStm::Repeat(inner_block, cond) => {
+                let mut calls = cond.calls();
+                calls.extend(inner_block.calls());
+                calls
+            },
+            AxStm::For(_, from, to, inner_block) => {
+                let mut calls = from.calls();
+                calls.extend(to.calls());
+                calls.extend(inner_block.calls());
+                calls
+            },
+            AxStm::Assert(cond) | AxStm::Assume(cond) => cond.calls(),
+            AxStm::Skip | AxStm::Abort => vec![],
+        }
+    }
+
     pub fn indent_string(&self, prefix: String) -> String {
         match self {
             AxStm::Skip => prefix + "skip",
             AxStm::Assign(v, aexp) => prefix + &format!("{} := {:?}", v, aexp),
+            AxStm::ArrAssign(a, index, aexp) => prefix + &format!("{}[{:?}] := {:?}", a, index, aexp),
             AxStm::If(cond, then_block, else_block) => {
                 let then_string = then_block.indent_string(prefix.clone() + "    ");
                 let else_string = else_block.indent_string(prefix.clone() + "    ");
 
-                format!("{}if {:?} then\n{}\nelse\n{}\nend", prefix, cond, then_string, else_string)
+                format!("{}if {:?} then\n{}\n{}else\n{}\n{}end", prefix, cond, then_string, prefix, else_string, prefix)
             },
             AxStm::While(cond, inner_block) => {
                 let inner_string = inner_block.indent_string(prefix.clone() + "    ");
 
-                format!("{}while {:?} do\n{}\nend", prefix, cond, inner_string)
+                format!("{}while {:?} do\n{}\n{}end", prefix, cond, inner_string, prefix)
+            },
+            AxStm::Assert(cond) => prefix + &format!("assert {:?}", cond),
+            AxStm::Assume(cond) => prefix + &format!("assume {:?}", cond),
+            AxStm::Abort => prefix + "abort",
+            AxStm::Repeat(inner_block, cond) => {
+                let inner_string = inner_block.indent_string(prefix.clone() + "    ");
+
+                format!("{}repeat\n{}\n{}until {:?}", prefix, inner_string, prefix, cond)
+            },
+            AxStm::For(v, from, to, inner_block) => {
+                let inner_string = inner_block.indent_string(prefix.clone() + "    ");
+
+                format!("{}for {} := {:?} to {:?} do\n{}\n{}end", prefix, v, from, to, inner_string, prefix)
             },
         }
     }
@@ -190,23 +360,118 @@ impl AxStm {
 //     }
 // }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum Stm {
     Assign(Var, Box<Aexp>),
+    ArrAssign(Var, Box<Aexp>, Box<Aexp>),
     Seq(Box<Stm>, Box<Stm>),
     Skip,
     If(Box<Bexp>, Box<Stm>, Box<Stm>),
     While(Box<Bexp>, Box<Stm>),
+    Assert(Box<Bexp>),
+    Assume(Box<Bexp>),
+    Abort,
+    Repeat(Box<Stm>, Box<Bexp>),
+    For(Var, Box<Aexp>, Box<Aexp>, Box<Stm>),
+}
+
+impl Stm {
+    /// The function and predicate applications in the statement, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match self {
+            Stm::Assign(_, aexp) => aexp.calls(),
+            Stm::ArrAssign(_, index, aexp) => {
+                let mut calls = index.calls();
+                calls.extend(aexp.calls());
+                calls
+            },
+            Stm::Seq(stm1, stm2) => {
+                let mut calls = stm1.calls();
+                calls.extend(stm2.calls());
+                calls
+            },
+            Stm::If(cond, then_stm, else_stm) => {
+                let mut calls = cond.calls();
+                calls.extend(then_stm.calls());
+                calls.extend(else_stm.calls());
+                calls
+            },
+            Stm::While(cond, inner) | Stm::Repeat(inner, cond) => {
+                let mut calls = cond.calls();
+                calls.extend(inner.calls());
+                calls
+            },
+            Stm::For(_, from, to, inner) => {
+                let mut calls = from.calls();
+                calls.extend(to.calls());
+                calls.extend(inner.calls());
+                calls
+            },
+            Stm::Assert(cond) | Stm::Assume(cond) => cond.calls(),
+            Stm::Skip | Stm::Abort => vec![],
+        }
+    }
+
+    /// The loop `for x := x + 1 to to do body end` executing the remaining iterations of a `for` loop
+    pub fn next_for_iteration(x: Var, to: i64, body: Box<Stm>) -> Box<Stm> {
+        let next = Aexp::Op(Box::new(Aexp::Var(x.clone())), Opcode::Add, Box::new(Aexp::Numeral(1)));
+        Box::new(Stm::For(x, Box::new(next), Box::new(Aexp::Numeral(to)), body))
+    }
+
+    pub fn indent_string(&self, prefix: String) -> String {
+        match self {
+            Stm::Assign(var, aexp) => prefix + &format!("{} := {:?}", var, aexp),
+            Stm::ArrAssign(arr, index, aexp) => prefix + &format!("{}[{:?}] := {:?}", arr, index, aexp),
+            // The parser nests sequences to the left, so only a sequence nested on the right needs parentheses
+            Stm::Seq(stm1, stm2) => match stm2.as_ref() {
+                Stm::Seq(_, _) => format!(
+                    "{};\n{}(\n{}\n{})", stm1.indent_string(prefix.clone()), prefix, stm2.indent_string(prefix.clone() + "    "), prefix
+                ),
+                _ => format!("{};\n{}", stm1.indent_string(prefix.clone()), stm2.indent_string(prefix)),
+            },
+            Stm::Skip => prefix + "skip",
+            Stm::If(cond, stm_then, stm_else) => {
+                let then_string = stm_then.indent_string(prefix.clone() + "    ");
+                let else_string = stm_else.indent_string(prefix.clone() + "    ");
+
+                format!("{}if {:?} then\n{}\n{}else\n{}\n{}end", prefix, cond, then_string, prefix, else_string, prefix)
+            },
+            Stm::While(cond, stm) => {
+                let inner_string = stm.indent_string(prefix.clone() + "    ");
+
+                format!("{}while {:?} do\n{}\n{}end", prefix, cond, inner_string, prefix)
+            },
+            Stm::Assert(cond) => prefix + &format!("assert {:?}", cond),
+            Stm::Assume(cond) => prefix + &format!("assume {:?}", cond),
+            Stm::Abort => prefix + "abort",
+            Stm::Repeat(stm, cond) => {
+                let inner_string = stm.indent_string(prefix.clone() + "    ");
+
+                format!("{}repeat\n{}\n{}until {:?}", prefix, inner_string, prefix, cond)
+            },
+            Stm::For(var, from, to, stm) => {
+                let inner_string = stm.indent_string(prefix.clone() + "    ");
+
+                format!("{}for {} := {:?} to {:?} do\n{}\n{}end", prefix, var, from, to, inner_string, prefix)
+            },
+        }
+    }
 }
 
 impl Debug for Stm {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Stm::Assign(var, aexp) => f.write_str(format!("{} := {:?}", var, aexp).as_str()),
+            Stm::ArrAssign(arr, index, aexp) => f.write_str(format!("{}[{:?}] := {:?}", arr, index, aexp).as_str()),
             Stm::Seq(stm1, stm2) => f.write_str(format!("({:?}; {:?})", stm1, stm2).as_str()),
             Stm::Skip => f.write_str("skip"),
             Stm::If(cond, stm_then, stm_else) => f.write_str(format!("if {:?} then {:?} else {:?} end", cond, stm_then, stm_else).as_str()),
             Stm::While(cond, stm) => f.write_str(format!("while {:?} do {:?} end", cond, stm).as_str()),
+            Stm::Assert(cond) => f.write_str(format!("assert {:?}", cond).as_str()),
+            Stm::Assume(cond) => f.write_str(format!("assume {:?}", cond).as_str()),
+            Stm::Abort => f.write_str("abort"),
+            Stm::Repeat(stm, cond) => f.write_str(format!("repeat {:?} until {:?}", stm, cond).as_str()),
+            Stm::For(var, from, to, stm) => f.write_str(format!("for {} := {:?} to {:?} do {:?} end", var, from, to, stm).as_str()),
         }
     }
 }
@@ -258,11 +523,35 @@ impl Debug for Ropcode {
     }
 }
 
+#[derive(Clone, Eq, PartialEq)]
+pub enum Quantifier {
+    Forall,
+    Exists,
+}
+
+impl Debug for Quantifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quantifier::Forall => f.write_str("forall"),
+            Quantifier::Exists => f.write_str("exists"),
+        }
+    }
+}
+
+/// The first variable `base1`, `base2`, ... that is not contained in `avoid`
+fn fresh_var(base: &Var, avoid: &HashSet<Var>) -> Var {
+    (1..).map(|n| format!("{}{}", base, n)).find(|v| !avoid.contains(v)).unwrap()
+}
+
 #[derive(Clone, Eq, PartialEq)]
 pub enum Bexp {
     Rop(Box<Aexp>, Ropcode, Box<Aexp>),
     Bop(Box<Bexp>, Bopcode, Box<Bexp>),
-    Not(Box<Bexp>)
+    Not(Box<Bexp>),
+    /// A quantifier binding an integer variable, only allowed in assertions
+    Quant(Quantifier, Var, Box<Bexp>),
+    /// An application of a predicate `p(x) := bexp`, only allowed in assertions
+    PredApp(String, Vec<Aexp>),
 }
 
 impl Bexp {
@@ -289,6 +578,21 @@ impl Bexp {
                     Bopcode::And => z3::ast::Bool::and(ctx, &[&left, &right]),
                     Bopcode::Or => z3::ast::Bool::or(ctx, &[&left, &right]),
                 }
+            },
+            Bexp::PredApp(pname, args) => {
+                let args: Vec<z3::ast::Dynamic<'a>> = args.iter().map(|arg| arg.to_z3_int(ctx, funcmap).into()).collect();
+                let args: Vec<_> = args.iter().collect();
+                let pred = funcmap.get(pname).unwrap();
+
+                pred.apply(args.as_slice()).as_bool().unwrap()
+            },
+            Bexp::Quant(q, x, body) => {
+                let x = z3::ast::Int::new_const(ctx, x.as_str());
+                let body = body.to_z3_bool(ctx, funcmap);
+                match q {
+                    Quantifier::Forall => z3::ast::forall_const(ctx, &[&x], &[], &body),
+                    Quantifier::Exists => z3::ast::exists_const(ctx, &[&x], &[], &body),
+                }
             }
         }
     }
@@ -306,6 +610,74 @@ impl Bexp {
                 let right = Box::new(right.substitute(var, new_aexp));
                 Bexp::Bop(left, bop, right)
             },
+            Bexp::PredApp(pname, args) => {
+                Bexp::PredApp(pname, args.into_iter().map(|arg| arg.substitute(var, new_aexp)).collect())
+            },
+            Bexp::Quant(q, x, body) => {
+                if &x == var || !body.free_vars().contains(var) {
+                    return Bexp::Quant(q, x, body);
+                }
+
+                let new_fv = new_aexp.free_vars();
+                if new_fv.contains(&x) {
+                    // Rename the bound variable so that it does not capture a free variable of `new_aexp`
+                    let mut avoid = body.free_vars();
+                    avoid.extend(new_fv);
+                    avoid.insert(var.clone());
+                    let fresh = fresh_var(&x, &avoid);
+                    let body = body.substitute(&x, &Aexp::Var(fresh.clone()));
+                    Bexp::Quant(q, fresh, Box::new(body.substitute(var, new_aexp)))
+                } else {
+                    Bexp::Quant(q, x, Box::new(body.substitute(var, new_aexp)))
+                }
+            },
+        }
+    }
+
+    /// Simultaneously substitutes the variables of `substitution`
+    pub fn substitute_all(self, substitution: &[(Var, Aexp)]) -> Self {
+        // Primed variables cannot occur in the source, so they serve as temporaries
+        let renamed = substitution.iter().fold(self, |bexp, (v, _)| {
+            bexp.substitute(v, &Aexp::Var(format!("{}'", v)))
+        });
+        substitution.iter().fold(renamed, |bexp, (v, new_aexp)| {
+            bexp.substitute(&format!("{}'", v), new_aexp)
+        })
+    }
+
+    pub fn substitute_array(self, arr: &Var, new_arr: &Array) -> Self {
+        match self {
+            Bexp::Not(bexp_inner) => Bexp::Not(Box::new(bexp_inner.substitute_array(arr, new_arr))),
+            Bexp::Rop(left, rop, right) => {
+                let left = Box::new(left.substitute_array(arr, new_arr));
+                let right = Box::new(right.substitute_array(arr, new_arr));
+                Bexp::Rop(left, rop, right)
+            },
+            Bexp::Bop(left, bop, right) => {
+                let left = Box::new(left.substitute_array(arr, new_arr));
+                let right = Box::new(right.substitute_array(arr, new_arr));
+                Bexp::Bop(left, bop, right)
+            },
+            Bexp::PredApp(pname, args) => {
+                Bexp::PredApp(pname, args.into_iter().map(|arg| arg.substitute_array(arr, new_arr)).collect())
+            },
+            Bexp::Quant(q, x, body) => {
+                if !body.free_arrays().contains(arr) {
+                    return Bexp::Quant(q, x, body);
+                }
+
+                let new_fv = new_arr.free_vars();
+                if new_fv.contains(&x) {
+                    // Rename the bound variable so that it does not capture a free variable of `new_arr`
+                    let mut avoid = body.free_vars();
+                    avoid.extend(new_fv);
+                    let fresh = fresh_var(&x, &avoid);
+                    let body = body.substitute(&x, &Aexp::Var(fresh.clone()));
+                    Bexp::Quant(q, fresh, Box::new(body.substitute_array(arr, new_arr)))
+                } else {
+                    Bexp::Quant(q, x, Box::new(body.substitute_array(arr, new_arr)))
+                }
+            },
         }
     }
 
@@ -317,7 +689,8 @@ impl Bexp {
             },
             Bexp::Bop(left, bop, right) => {
                 format!("({} {} {})", bop.sexp_string(), left.sexp_string(), right.sexp_string())
-            }
+            },
+            _ => panic!("Unsupported sexp_string: {:?}", self),
         }
     }
 
@@ -326,12 +699,22 @@ impl Bexp {
             Bexp::Rop(left, _, right) => left.can_egg() && right.can_egg(),
             Bexp::Bop(left, _, right) => left.can_egg() && right.can_egg(),
             Bexp::Not(i) => i.can_egg(),
+            Bexp::Quant(_, _, _) | Bexp::PredApp(_, _) => false,
         }
     }
 
     pub fn pretty_string(&self) -> String {
         match &self {
-            Bexp::Rop(left, rop, right) => format!("{} {:?} {}", left.pretty_string(), rop, right.pretty_string()),
+            Bexp::Rop(left, rop, right) => {
+                // Conditional expressions extend as far right as possible, so they need parentheses here
+                let paren = |aexp: &Aexp| if aexp.precedence() == 0 {
+                    format!("({})", aexp.pretty_string())
+                } else {
+                    aexp.pretty_string()
+                };
+
+                format!("{} {:?} {}", paren(left), rop, paren(right))
+            },
             Bexp::Not(not) => {
                 if not.precedence() < self.precedence() {
                     format!("not ({})", not.pretty_string())
@@ -359,7 +742,13 @@ impl Bexp {
                 };
 
                 format!("{} {:?} {}", left_string, bop, right_string)
-            }
+            },
+            // The body of a quantifier extends as far right as possible
+            Bexp::Quant(q, x, body) => format!("{:?} {}. {}", q, x, body.pretty_string()),
+            Bexp::PredApp(pname, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.pretty_string()).collect();
+                format!("{}({})", pname, args.join(", "))
+            },
         }
     }
 
@@ -371,12 +760,35 @@ impl Bexp {
         true // we don't have right-associative arithmetic operators
     }
 
+    fn has_pred_app_or_quant(&self) -> bool {
+        match self {
+            Bexp::Rop(_, _, _) => false,
+            Bexp::Bop(left, _, right) => left.has_pred_app_or_quant() || right.has_pred_app_or_quant(),
+            Bexp::Not(inner) => inner.has_pred_app_or_quant(),
+            Bexp::Quant(_, _, _) | Bexp::PredApp(_, _) => true,
+        }
+    }
+
     fn precedence(&self) -> u32 {
         match &self {
             Bexp::Rop(_, _, _) => 4,
+            Bexp::PredApp(_, _) => 4,
             Bexp::Not(_) => 3,
             Bexp::Bop(_, Bopcode::And, _) => 2,
             Bexp::Bop(_, Bopcode::Or, _) => 1,
+            Bexp::Quant(_, _, _) => 0,
+        }
+    }
+
+    /// The operands of the top-level conjunctions, e.g. `[a, b, c]` for `a and (b and c)`
+    pub fn conjuncts(&self) -> Vec<&Bexp> {
+        match self {
+            Bexp::Bop(left, Bopcode::And, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            },
+            other => vec![other],
         }
     }
 
@@ -392,7 +804,101 @@ impl Bexp {
                 left_fv.extend(right.free_vars());
                 left_fv
             }
-            Bexp::Not(inner) => inner.free_vars()
+            Bexp::Not(inner) => inner.free_vars(),
+            Bexp::Quant(_, x, body) => {
+                let mut body_fv = body.free_vars();
+                body_fv.remove(x);
+                body_fv
+            }
+            Bexp::PredApp(_, args) => {
+                args.iter().flat_map(|arg| arg.free_vars().into_iter()).collect::<HashSet<_>>()
+            }
+        }
+    }
+
+    /// All function and predicate applications, including nested ones
+    pub fn func_apps(&self) -> Vec<(String, Vec<Aexp>)> {
+        match self {
+            Bexp::Rop(left, _, right) => {
+                let mut apps = left.func_apps();
+                apps.extend(right.func_apps());
+                apps
+            }
+            Bexp::Bop(left, _, right) => {
+                let mut apps = left.func_apps();
+                apps.extend(right.func_apps());
+                apps
+            }
+            Bexp::Not(inner) => inner.func_apps(),
+            Bexp::Quant(_, _, body) => body.func_apps(),
+            Bexp::PredApp(pname, args) => {
+                let mut apps = vec![(pname.clone(), args.clone())];
+                apps.extend(args.iter().flat_map(|arg| arg.func_apps()));
+                apps
+            }
+        }
+    }
+
+    /// The function and predicate applications as `(name, number of arguments, whether it is a predicate)`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match self {
+            Bexp::Rop(left, _, right) => {
+                let mut calls = left.calls();
+                calls.extend(right.calls());
+                calls
+            }
+            Bexp::Bop(left, _, right) => {
+                let mut calls = left.calls();
+                calls.extend(right.calls());
+                calls
+            }
+            Bexp::Not(inner) => inner.calls(),
+            Bexp::Quant(_, _, body) => body.calls(),
+            Bexp::PredApp(pname, args) => {
+                let mut calls = vec![(pname.clone(), args.len(), true)];
+                calls.extend(args.iter().flat_map(|arg| arg.calls()));
+                calls
+            }
+        }
+    }
+
+    pub fn free_arrays(&self) -> HashSet<Var> {
+        match self {
+            Bexp::Rop(left, _, right) => {
+                let mut left_fa = left.free_arrays();
+                left_fa.extend(right.free_arrays());
+                left_fa
+            }
+            Bexp::Bop(left, _, right) => {
+                let mut left_fa = left.free_arrays();
+                left_fa.extend(right.free_arrays());
+                left_fa
+            }
+            Bexp::Not(inner) => inner.free_arrays(),
+            Bexp::Quant(_, _, body) => body.free_arrays(),
+            Bexp::PredApp(_, args) => {
+                args.iter().flat_map(|arg| arg.free_arrays().into_iter()).collect::<HashSet<_>>()
+            }
+        }
+    }
+
+    /// The entries `(a, i)` of the free arrays `a` that are read, where the index `i` is in terms of the free variables
+    pub fn array_reads(&self) -> Vec<(Var, Aexp)> {
+        match self {
+            Bexp::Rop(left, _, right) => {
+                let mut left_reads = left.array_reads();
+                left_reads.extend(right.array_reads());
+                left_reads
+            }
+            Bexp::Bop(left, _, right) => {
+                let mut left_reads = left.array_reads();
+                left_reads.extend(right.array_reads());
+                left_reads
+            }
+            Bexp::Not(inner) => inner.array_reads(),
+            // Reads depending on the bound variable cannot be determined
+            Bexp::Quant(_, x, body) => body.array_reads().into_iter().filter(|(_, index)| !index.free_vars().contains(x)).collect(),
+            Bexp::PredApp(_, args) => args.iter().flat_map(|arg| arg.array_reads()).collect(),
         }
     }
 
@@ -417,6 +923,7 @@ pub enum Opcode {
     Add,
     Sub,
     Mul,
+    Div,
     Mod,
     Pow,
 }
@@ -427,6 +934,7 @@ impl Debug for Opcode {
             Opcode::Add => f.write_str("+"),
             Opcode::Sub => f.write_str("-"),
             Opcode::Mul => f.write_str("*"),
+            Opcode::Div => f.write_str("/"),
             Opcode::Mod => f.write_str("%"),
             Opcode::Pow => f.write_str("^"),
         }
@@ -446,19 +954,100 @@ impl Debug for Opcode {
 //     }
 // }
 
+/// The body of a function definition, either an integer-valued function `f(x) { aexp }`
+/// or a predicate `p(x) := bexp`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ImpFuncBody {
+    Int(Aexp),
+    Bool(Bexp),
+    /// An integer-valued function defined by the clauses `| guard = aexp`, tried from top to bottom
+    Clauses(Vec<FuncClause>),
+}
+
+/// A clause `| guard = aexp` of a function definition, the guard of the clause `| otherwise = aexp` is `None`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FuncClause {
+    pub guard: Option<Bexp>,
+    pub body: Aexp,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ImpFuncDef {
     pub name: String,
     pub args: Vec<Var>,
-    pub body: Aexp,
+    /// The measure annotated with `decreases`, which proves that the function terminates
+    pub decreases: Option<Aexp>,
+    pub body: ImpFuncBody,
 }
 
 impl ImpFuncDef {
+    pub fn indent_string(&self, prefix: String) -> String {
+        let decreases = match &self.decreases {
+            Some(measure) => format!(" decreases {:?}", measure),
+            None => "".to_owned(),
+        };
+
+        match &self.body {
+            ImpFuncBody::Int(body) => {
+                format!("{}{}({}){} {{\n{}    {:?}\n{}}}", prefix, self.name, self.args.join(", "), decreases, prefix, body, prefix)
+            },
+            ImpFuncBody::Bool(body) => format!("{}{}({}){} := {:?}", prefix, self.name, self.args.join(", "), decreases, body),
+            ImpFuncBody::Clauses(clauses) => {
+                let clause_strings: Vec<String> = clauses.iter().map(|clause| match &clause.guard {
+                    // Predicate calls and quantifiers are only parsed inside parentheses in a guard
+                    Some(guard) if guard.has_pred_app_or_quant() => format!("{}    | ({:?}) = {:?}", prefix, guard, clause.body),
+                    Some(guard) => format!("{}    | {:?} = {:?}", prefix, guard, clause.body),
+                    None => format!("{}    | otherwise = {:?}", prefix, clause.body),
+                }).collect();
+
+                format!("{}{}({}){}\n{}", prefix, self.name, self.args.join(", "), decreases, clause_strings.join("\n"))
+            },
+        }
+    }
+
+    /// The body of an integer-valued function, where clauses are desugared into nested `? :` expressions.
+    /// The guard of the last clause is kept unless it is `otherwise`, so that arguments no clause applies to map to
+    /// the unconstrained value `f@undefined`.
+    pub fn int_body(&self) -> Option<Aexp> {
+        match &self.body {
+            ImpFuncBody::Int(body) => Some(body.clone()),
+            ImpFuncBody::Bool(_) => None,
+            ImpFuncBody::Clauses(clauses) => {
+                let undefined = Aexp::Var(format!("{}@undefined", self.name));
+
+                Some(clauses.iter().rev().fold(undefined, |else_aexp, clause| match &clause.guard {
+                    Some(guard) => Aexp::Ite(Box::new(guard.clone()), Box::new(clause.body.clone()), Box::new(else_aexp)),
+                    None => clause.body.clone(),
+                }))
+            },
+        }
+    }
+
+    /// All function and predicate applications in the body
+    pub fn func_apps(&self) -> Vec<(String, Vec<Aexp>)> {
+        match &self.body {
+            ImpFuncBody::Bool(body) => body.func_apps(),
+            _ => self.int_body().unwrap().func_apps(),
+        }
+    }
+
+    /// All function and predicate applications in the body, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match &self.body {
+            ImpFuncBody::Bool(body) => body.calls(),
+            _ => self.int_body().unwrap().calls(),
+        }
+    }
+
     pub fn to_z3_func_decl<'ctx>(&self, ctx: &'ctx z3::Context) -> z3::RecFuncDecl<'ctx> {
         // let domain: Vec<_> = self.args.iter().map(|_| &Sort::int(&ctx)).collect();
-        let domain = vec![Sort::int(&ctx); self.args.len()];
+        let domain = vec![Sort::int(ctx); self.args.len()];
         let domain: Vec<_> = domain.iter().collect();
-        let f = RecFuncDecl::new(ctx, self.name.as_str(), domain.as_slice(), &Sort::int(&ctx));
+        let range = match self.body {
+            ImpFuncBody::Bool(_) => Sort::bool(ctx),
+            _ => Sort::int(ctx),
+        };
+        let f = RecFuncDecl::new(ctx, self.name.as_str(), domain.as_slice(), &range);
 
         f
     }
@@ -466,19 +1055,67 @@ impl ImpFuncDef {
     pub fn define<'a>(&self, ctx: &'a z3::Context, funcmap: &FuncMap<'a>) {
         let mut f = funcmap.get(&self.name).unwrap();
 
-        let args: Vec<_> = self.args.iter().map(|arg| z3::ast::Dynamic::from(z3::ast::Int::new_const(&ctx, arg.as_str()))).collect();
+        let args: Vec<_> = self.args.iter().map(|arg| z3::ast::Dynamic::from(z3::ast::Int::new_const(ctx, arg.as_str()))).collect();
         let args: Vec<_> = args.iter().collect();
-        f.add_def(args.as_slice(), &self.body.to_z3_int(&ctx, funcmap));
+        match &self.body {
+            ImpFuncBody::Bool(body) => f.add_def(args.as_slice(), &body.to_z3_bool(ctx, funcmap)),
+            _ => f.add_def(args.as_slice(), &self.int_body().unwrap().to_z3_int(ctx, funcmap)),
+        }
     }
 }
 
 type FuncMap<'ctx> = HashMap<String, z3::RecFuncDecl<'ctx>>;
 
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum LemmaKind {
+    /// Assumed without a proof
+    Axiom,
+    /// Proven directly by Z3
+    Lemma,
+    /// Proven by induction on the given variable
+    Induction(Var),
+}
+
+/// A fact `lemma name: bexp` or `axiom name: bexp` about the functions of a proof, which holds for all values
+/// of its free variables. Axioms and proven lemmas are assumed when verifying entailments
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ImpLemma {
+    pub name: String,
+    pub kind: LemmaKind,
+    pub body: Bexp,
+}
+
+impl ImpLemma {
+    pub fn indent_string(&self, prefix: String) -> String {
+        match &self.kind {
+            LemmaKind::Axiom => format!("{}axiom {}: {:?}", prefix, self.name, self.body),
+            LemmaKind::Lemma => format!("{}lemma {}: {:?}", prefix, self.name, self.body),
+            LemmaKind::Induction(v) => format!("{}lemma {} by induction on {}: {:?}", prefix, self.name, v, self.body),
+        }
+    }
+
+    /// The universal closure of the lemma's body
+    pub fn to_z3_axiom<'a>(&self, ctx: &'a z3::Context, funcmap: &FuncMap<'a>) -> z3::ast::Bool<'a> {
+        let vars: Vec<Int<'a>> = self.body.free_vars().iter().map(|v| Int::new_const(ctx, v.as_str())).collect();
+        let arrays: Vec<z3::ast::Array<'a>> = self.body.free_arrays().iter().map(|a| {
+            z3::ast::Array::new_const(ctx, a.as_str(), &Sort::int(ctx), &Sort::int(ctx))
+        }).collect();
+
+        let mut bounds: Vec<&dyn Ast<'a>> = vars.iter().map(|v| v as &dyn Ast<'a>).collect();
+        bounds.extend(arrays.iter().map(|a| a as &dyn Ast<'a>));
+
+        z3::ast::forall_const(ctx, bounds.as_slice(), &[], &self.body.to_z3_bool(ctx, funcmap))
+    }
+}
+
 #[derive(Clone, Eq, PartialEq)]
 pub enum Aexp {
     Numeral(i64),
     Var(Var),
     Op(Box<Aexp>, Opcode, Box<Aexp>),
+    Neg(Box<Aexp>),
+    /// Reading the entry `a[i]` of an array
+    Select(Box<Array>, Box<Aexp>),
     FuncApp(String, Vec<Aexp>),
     Ite(Box<Bexp>, Box<Aexp>, Box<Aexp>),
     // Unop(Box<Aexp>, Unopcode),
@@ -496,12 +1133,20 @@ impl Aexp {
                     Opcode::Add => z3::ast::Int::add(ctx, &[&left, &right]),
                     Opcode::Sub => z3::ast::Int::sub(ctx, &[&left, &right]),
                     Opcode::Mul => z3::ast::Int::mul(ctx, &[&left, &right]),
+                    Opcode::Div => left.div(&right),
                     Opcode::Mod => left.modulo(&right),
                     Opcode::Pow => left.power(&right).to_real().to_int(),
                 }
                 // println!("what: {:?} sort: {:?}", res, res.get_sort());
                 // res
             },
+            Aexp::Neg(inner) => inner.to_z3_int(ctx, funcmap).unary_minus(),
+            Aexp::Select(arr, index) => {
+                let arr = arr.to_z3_array(ctx, funcmap);
+                let index = index.to_z3_int(ctx, funcmap);
+
+                arr.select(&index).as_int().unwrap()
+            },
             Aexp::FuncApp(fname, args) => {
                 let args: Vec<z3::ast::Dynamic<'a>> = args.into_iter().map(|arg| {
                     arg.to_z3_int(&ctx, &funcmap).into()
@@ -533,14 +1178,16 @@ impl Aexp {
 
                 format!("({:?} {} {})", op, left.sexp_string(), right.sexp_string())
             },
+            Aexp::Neg(inner) => format!("(~ {})", inner.sexp_string()),
             _ => panic!("Unsupported sexp_string: {:?}", self),
         }
     }
 
     pub fn can_egg(&self) -> bool {
         match self {
-            Aexp::FuncApp(_, _) | Aexp::Ite(_, _, _) => false,
+            Aexp::FuncApp(_, _) | Aexp::Ite(_, _, _) | Aexp::Select(_, _) => false,
             Aexp::Op(left, _, right) => left.can_egg() && right.can_egg(),
+            Aexp::Neg(inner) => inner.can_egg(),
             _ => true,
         }
     }
@@ -570,13 +1217,21 @@ impl Aexp {
 
                 format!("{} {:?} {}", left_string, op, right_string)
             },
+            Aexp::Neg(inner) => {
+                if self.precedence() > inner.precedence() {
+                    format!("-({})", inner.pretty_string())
+                } else {
+                    format!("-{}", inner.pretty_string())
+                }
+            },
+            Aexp::Select(arr, index) => format!("{}[{}]", arr.pretty_string(), index.pretty_string()),
             Aexp::FuncApp(fname, args) => {
                 let args: Vec<String> = args.into_iter().map(|arg| arg.pretty_string()).collect();
                 let arg_string = args.join(", ");
                 format!("{}({})", fname, arg_string)
             },
             Aexp::Ite(cond, t, e) => {
-                format!("{} ? {} : {}", cond.pretty_string(), t.pretty_string(), e.pretty_string())
+                format!("({}) ? {} : {}", cond.pretty_string(), t.pretty_string(), e.pretty_string())
             },
         }
     }
@@ -591,14 +1246,19 @@ impl Aexp {
 
     fn precedence(&self) -> u32 {
         match &self {
-            Aexp::Numeral(_) => 4,
-            Aexp::Var(_) => 4,
+            // Negative literals are printed with a leading minus and thus bind like negation
+            Aexp::Numeral(num) if *num < 0 => 3,
+            Aexp::Numeral(_) => 5,
+            Aexp::Var(_) => 5,
             Aexp::Op(_, Opcode::Add, _) => 1,
             Aexp::Op(_, Opcode::Sub, _) => 1,
             Aexp::Op(_, Opcode::Mul, _) => 2,
+            Aexp::Op(_, Opcode::Div, _) => 2,
             Aexp::Op(_, Opcode::Mod, _) => 2,
-            Aexp::Op(_, Opcode::Pow, _) => 3,
-            Aexp::FuncApp(_, _) => 4,
+            Aexp::Neg(_) => 3,
+            Aexp::Op(_, Opcode::Pow, _) => 4,
+            Aexp::Select(_, _) => 5,
+            Aexp::FuncApp(_, _) => 5,
             Aexp::Ite(_, _, _) => 0,
         }
     }
@@ -611,6 +1271,10 @@ impl Aexp {
                 let right = right.substitute(var, new_aexp);
                 Aexp::Op(Box::new(left), op, Box::new(right))
             },
+            Aexp::Neg(inner) => Aexp::Neg(Box::new(inner.substitute(var, new_aexp))),
+            Aexp::Select(arr, index) => {
+                Aexp::Select(Box::new(arr.substitute(var, new_aexp)), Box::new(index.substitute(var, new_aexp)))
+            },
             Aexp::Ite(cond, t, e) => {
                 let cond = Box::new(cond.substitute(var, new_aexp));
                 let t = Box::new(t.substitute(var, new_aexp));
@@ -624,6 +1288,41 @@ impl Aexp {
         }
     }
 
+    /// Simultaneously substitutes the variables of `substitution`
+    pub fn substitute_all(self, substitution: &[(Var, Aexp)]) -> Self {
+        let renamed = substitution.iter().fold(self, |aexp, (v, _)| {
+            aexp.substitute(v, &Aexp::Var(format!("{}'", v)))
+        });
+        substitution.iter().fold(renamed, |aexp, (v, new_aexp)| {
+            aexp.substitute(&format!("{}'", v), new_aexp)
+        })
+    }
+
+    pub fn substitute_array(self, arr: &Var, new_arr: &Array) -> Self {
+        match self {
+            Aexp::Op(left, op, right) => {
+                let left = left.substitute_array(arr, new_arr);
+                let right = right.substitute_array(arr, new_arr);
+                Aexp::Op(Box::new(left), op, Box::new(right))
+            },
+            Aexp::Neg(inner) => Aexp::Neg(Box::new(inner.substitute_array(arr, new_arr))),
+            Aexp::Select(this_arr, index) => {
+                Aexp::Select(Box::new(this_arr.substitute_array(arr, new_arr)), Box::new(index.substitute_array(arr, new_arr)))
+            },
+            Aexp::Ite(cond, t, e) => {
+                let cond = Box::new(cond.substitute_array(arr, new_arr));
+                let t = Box::new(t.substitute_array(arr, new_arr));
+                let e = Box::new(e.substitute_array(arr, new_arr));
+                Aexp::Ite(cond, t, e)
+            }
+            Aexp::FuncApp(fname, args) => {
+                Aexp::FuncApp(fname, args.into_iter().map(|arg| arg.substitute_array(arr, new_arr)).collect())
+            }
+            _ => self,
+        }
+    }
+
+    /// The free integer variables, array names are not included (see `free_arrays`)
     pub fn free_vars(&self) -> HashSet<Var> {
         match self {
             Aexp::Numeral(_) => HashSet::new(),
@@ -633,6 +1332,12 @@ impl Aexp {
                 left_fv.extend(right.free_vars());
                 left_fv
             }
+            Aexp::Neg(inner) => inner.free_vars(),
+            Aexp::Select(arr, index) => {
+                let mut arr_fv = arr.free_vars();
+                arr_fv.extend(index.free_vars());
+                arr_fv
+            }
             Aexp::FuncApp(_, args) => {
                 args.into_iter().map(|arg| arg.free_vars().into_iter()).flatten().collect::<HashSet<_>>()
             }
@@ -644,6 +1349,115 @@ impl Aexp {
             }
         }
     }
+
+    /// All function applications, including nested ones
+    pub fn func_apps(&self) -> Vec<(String, Vec<Aexp>)> {
+        match self {
+            Aexp::Numeral(_) | Aexp::Var(_) => vec![],
+            Aexp::Op(left, _, right) => {
+                let mut apps = left.func_apps();
+                apps.extend(right.func_apps());
+                apps
+            }
+            Aexp::Neg(inner) => inner.func_apps(),
+            Aexp::Select(arr, index) => {
+                let mut apps = arr.func_apps();
+                apps.extend(index.func_apps());
+                apps
+            }
+            Aexp::FuncApp(fname, args) => {
+                let mut apps = vec![(fname.clone(), args.clone())];
+                apps.extend(args.iter().flat_map(|arg| arg.func_apps()));
+                apps
+            }
+            Aexp::Ite(cond, t, e) => {
+                let mut apps = cond.func_apps();
+                apps.extend(t.func_apps());
+                apps.extend(e.func_apps());
+                apps
+            }
+        }
+    }
+
+    /// The function and predicate applications, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match self {
+            Aexp::Numeral(_) | Aexp::Var(_) => vec![],
+            Aexp::Op(left, _, right) => {
+                let mut calls = left.calls();
+                calls.extend(right.calls());
+                calls
+            }
+            Aexp::Neg(inner) => inner.calls(),
+            Aexp::Select(arr, index) => {
+                let mut calls = arr.calls();
+                calls.extend(index.calls());
+                calls
+            }
+            Aexp::FuncApp(fname, args) => {
+                let mut calls = vec![(fname.clone(), args.len(), false)];
+                calls.extend(args.iter().flat_map(|arg| arg.calls()));
+                calls
+            }
+            Aexp::Ite(cond, t, e) => {
+                let mut calls = cond.calls();
+                calls.extend(t.calls());
+                calls.extend(e.calls());
+                calls
+            }
+        }
+    }
+
+    pub fn free_arrays(&self) -> HashSet<Var> {
+        match self {
+            Aexp::Numeral(_) | Aexp::Var(_) => HashSet::new(),
+            Aexp::Op(left, _, right) => {
+                let mut left_fa = left.free_arrays();
+                left_fa.extend(right.free_arrays());
+                left_fa
+            }
+            Aexp::Neg(inner) => inner.free_arrays(),
+            Aexp::Select(arr, index) => {
+                let mut arr_fa = arr.free_arrays();
+                arr_fa.extend(index.free_arrays());
+                arr_fa
+            }
+            Aexp::FuncApp(_, args) => {
+                args.iter().flat_map(|arg| arg.free_arrays().into_iter()).collect::<HashSet<_>>()
+            }
+            Aexp::Ite(cond, t, e) => {
+                let mut cond_fa = cond.free_arrays();
+                cond_fa.extend(t.free_arrays());
+                cond_fa.extend(e.free_arrays());
+                cond_fa
+            }
+        }
+    }
+
+    /// The entries `(a, i)` of the free arrays `a` that are read, where the index `i` is in terms of the free variables
+    pub fn array_reads(&self) -> Vec<(Var, Aexp)> {
+        match self {
+            Aexp::Numeral(_) | Aexp::Var(_) => vec![],
+            Aexp::Op(left, _, right) => {
+                let mut left_reads = left.array_reads();
+                left_reads.extend(right.array_reads());
+                left_reads
+            }
+            Aexp::Neg(inner) => inner.array_reads(),
+            Aexp::Select(arr, index) => {
+                let mut arr_reads = arr.array_reads_at(index);
+                arr_reads.extend(index.array_reads());
+                arr_reads
+            }
+            Aexp::FuncApp(_, args) => args.iter().flat_map(|arg| arg.array_reads()).collect(),
+            Aexp::Ite(cond, then_aexp, else_aexp) => {
+                let mut cond_reads = cond.array_reads();
+                cond_reads.extend(then_aexp.array_reads());
+                cond_reads.extend(else_aexp.array_reads());
+                cond_reads
+            }
+        }
+    }
 }
 
 impl Debug for Aexp {
@@ -658,3 +1472,128 @@ impl Debug for Aexp {
     //     }
     // }
 }
+
+/// A one-dimensional integer array, either an array variable or the array `a[i := e]` that is equal to `a`
+/// except at index `i`, where its entry is `e`
+#[derive(Clone, Eq, PartialEq)]
+pub enum Array {
+    Var(Var),
+    Store(Box<Array>, Box<Aexp>, Box<Aexp>),
+}
+
+impl Array {
+    pub fn to_z3_array<'a>(&self, ctx: &'a z3::Context, funcmap: &FuncMap<'a>) -> z3::ast::Array<'a> {
+        match self {
+            Array::Var(arr) => z3::ast::Array::new_const(ctx, arr.as_str(), &Sort::int(ctx), &Sort::int(ctx)),
+            Array::Store(arr, index, value) => {
+                let arr = arr.to_z3_array(ctx, funcmap);
+                let index = index.to_z3_int(ctx, funcmap);
+                let value = value.to_z3_int(ctx, funcmap);
+
+                arr.store(&index, &value)
+            }
+        }
+    }
+
+    pub fn pretty_string(&self) -> String {
+        match self {
+            Array::Var(arr) => arr.to_string(),
+            Array::Store(arr, index, value) => {
+                format!("{}[{} := {}]", arr.pretty_string(), index.pretty_string(), value.pretty_string())
+            }
+        }
+    }
+
+    pub fn substitute(self, var: &Var, new_aexp: &Aexp) -> Self {
+        match self {
+            Array::Var(_) => self,
+            Array::Store(arr, index, value) => {
+                let arr = Box::new(arr.substitute(var, new_aexp));
+                let index = Box::new(index.substitute(var, new_aexp));
+                let value = Box::new(value.substitute(var, new_aexp));
+                Array::Store(arr, index, value)
+            }
+        }
+    }
+
+    pub fn substitute_array(self, arr: &Var, new_arr: &Array) -> Self {
+        match self {
+            Array::Var(this_arr) if &this_arr == arr => new_arr.clone(),
+            Array::Var(_) => self,
+            Array::Store(this_arr, index, value) => {
+                let this_arr = Box::new(this_arr.substitute_array(arr, new_arr));
+                let index = Box::new(index.substitute_array(arr, new_arr));
+                let value = Box::new(value.substitute_array(arr, new_arr));
+                Array::Store(this_arr, index, value)
+            }
+        }
+    }
+
+    pub fn free_vars(&self) -> HashSet<Var> {
+        match self {
+            Array::Var(_) => HashSet::new(),
+            Array::Store(arr, index, value) => {
+                let mut arr_fv = arr.free_vars();
+                arr_fv.extend(index.free_vars());
+                arr_fv.extend(value.free_vars());
+                arr_fv
+            }
+        }
+    }
+
+    pub fn func_apps(&self) -> Vec<(String, Vec<Aexp>)> {
+        match self {
+            Array::Var(_) => vec![],
+            Array::Store(arr, index, value) => {
+                let mut apps = arr.func_apps();
+                apps.extend(index.func_apps());
+                apps.extend(value.func_apps());
+                apps
+            }
+        }
+    }
+
+    /// The function and predicate applications, see `Bexp::calls`
+    pub fn calls(&self) -> Vec<(String, usize, bool)> {
+        match self {
+            Array::Var(_) => vec![],
+            Array::Store(arr, index, value) => {
+                let mut calls = arr.calls();
+                calls.extend(index.calls());
+                calls.extend(value.calls());
+                calls
+            }
+        }
+    }
+
+    pub fn free_arrays(&self) -> HashSet<Var> {
+        match self {
+            Array::Var(arr) => HashSet::from([arr.clone()]),
+            Array::Store(arr, index, value) => {
+                let mut arr_fa = arr.free_arrays();
+                arr_fa.extend(index.free_arrays());
+                arr_fa.extend(value.free_arrays());
+                arr_fa
+            }
+        }
+    }
+
+    /// The entries of the free arrays read when reading the array at `index`, see `Aexp::array_reads`
+    pub fn array_reads_at(&self, index: &Aexp) -> Vec<(Var, Aexp)> {
+        match self {
+            Array::Var(arr) => vec![(arr.clone(), index.clone())],
+            Array::Store(arr, store_index, value) => {
+                let mut arr_reads = arr.array_reads_at(index);
+                arr_reads.extend(store_index.array_reads());
+                arr_reads.extend(value.array_reads());
+                arr_reads
+            }
+        }
+    }
+}
+
+impl Debug for Array {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pretty_string())
+    }
+}
@@ -52,6 +52,18 @@ pub fn verify_block_except_cons_partial(AxBlock(AssertionChain(first), rem): &Ax
                     }.into()));
                 }
             },
+            AxStm::ArrAssign(arr, index, aexp) => {
+                // { Q[a ↦ a[i := e]] } a[i] := e { Q }
+                let new_arr = Array::Store(Box::new(Array::Var(arr.clone())), Box::new(index.clone()), Box::new(aexp.clone()));
+                let pre_must = post.clone().substitute_array(arr, &new_arr);
+                if *pre != pre_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: pre.clone(),
+                        expected: pre_must,
+                    }.into()));
+                }
+            },
             AxStm::If(
                 cond,
                 AxBlock(AssertionChain(then_pre_chain), then_rem),
@@ -130,6 +142,52 @@ pub fn verify_block_except_cons_partial(AxBlock(AssertionChain(first), rem): &Ax
                 }
                 // TODO: Write helper function err_must_eq for ^^ above structure
             },
+            AxStm::Assert(cond) => {
+                // { b and (Q) } assert b { Q }
+                let pre_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(post.clone()));
+                if *pre != pre_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: pre.clone(),
+                        expected: pre_must,
+                    }.into()))
+                }
+            },
+            AxStm::Assume(cond) => {
+                // { P } assume b { b and (P) }
+                let post_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(pre.clone()));
+                if *post != post_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: post.clone(),
+                        expected: post_must,
+                    }.into()))
+                }
+            },
+            // { P } abort { Q }
+            AxStm::Abort => (),
+            AxStm::Repeat(AxBlock(AssertionChain(inner_pre_chain), inner_rem), cond) => {
+                // { P } repeat { P } s { Q } until b { b and (Q) }, with side-condition not b and (Q) ⊨ P
+                let inner_pre = inner_pre_chain.first().unwrap();
+                let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
+
+                if inner_pre != pre {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: inner_pre.clone(),
+                        expected: pre.clone(),
+                    }.into()))
+                }
+                let post_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(inner_post.clone()));
+                if *post != post_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: post.clone(),
+                        expected: post_must,
+                    }.into()))
+                }
+            },
+            AxStm::For(..) => ea(verify_for_structure(stm, pre, post)),
         }
         pre = post_chain.last().unwrap();
     }
@@ -141,23 +199,85 @@ pub fn verify_block_except_cons_partial(AxBlock(AssertionChain(first), rem): &Ax
     Ok(())
 }
 
-pub fn verify_cons_partial(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+/// The precondition `Q[x ↦ e]` the rule of an assignment `x := e` (or `a[i] := e`) requires for its postcondition `Q`
+fn assignment_pre(stm: &AxStm, post: &Bexp) -> Option<Bexp> {
+    match stm {
+        AxStm::Assign(v, aexp) => Some(post.clone().substitute(v, aexp)),
+        AxStm::ArrAssign(arr, index, aexp) => {
+            let new_arr = Array::Store(Box::new(Array::Var(arr.clone())), Box::new(index.clone()), Box::new(aexp.clone()));
+            Some(post.clone().substitute_array(arr, &new_arr))
+        },
+        _ => None,
+    }
+}
+
+/// The consequence steps `⊨ { Q[x ↦ e] }` to insert before every assignment `x := e` with postcondition `Q` whose
+/// precondition differs, so that only the entailment remains to be proven. Each step is given by the index of the
+/// assertion it follows, where the assertions of the outline are counted in the order they are written, starting at
+/// `index`. Since the steps are appended to the chain before an assignment, the outer structure is unchanged.
+pub fn assignment_fixes(AxBlock(first, rem): &AxBlock, index: &mut usize) -> Vec<(usize, Bexp)> {
+    let mut fixes = vec![];
+    *index += first.0.len();
+
+    let mut pre_chain = first;
+    for (stm, post_chain) in rem {
+        match stm {
+            AxStm::If(_, then_block, else_block) => {
+                fixes.extend(assignment_fixes(then_block, index));
+                fixes.extend(assignment_fixes(else_block, index));
+            },
+            AxStm::While(_, inner_block) | AxStm::Repeat(inner_block, _) | AxStm::For(_, _, _, inner_block) => {
+                fixes.extend(assignment_fixes(inner_block, index));
+            },
+            stm => if let Some(pre_must) = assignment_pre(stm, post_chain.0.first().unwrap()) {
+                if *pre_chain.0.last().unwrap() != pre_must {
+                    fixes.push((*index - 1, pre_must));
+                }
+            },
+        }
+
+        *index += post_chain.0.len();
+        pre_chain = post_chain;
+    }
+
+    fixes
+}
+
+pub fn verify_cons_partial(cfg: &z3::Config, prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma]) -> Result<()> {
+    verify_cons_partial_in(cfg, prog, funcdefs, lemmas, &prog.program_vars())
+}
+
+/// Verifies the entailments of a block of the program, whose variables are `prog_vars`
+fn verify_cons_partial_in(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], prog_vars: &HashSet<Var>) -> Result<()> {
     let mut errs = ImpErrors(vec![]);
     let mut ea = |r: Result<()>| {
         err_acc(&mut errs, r);
     };
 
-    ea(verify_assertion_chain(&cfg, first, funcdefs));
+    ea(verify_assertion_chain(&cfg, first, funcdefs, lemmas, prog_vars));
     for (stm, post_chain) in rem {
         match stm {
-            AxStm::While(_, inner_block) => ea(verify_cons_partial(cfg, inner_block, funcdefs)),
+            AxStm::While(_, inner_block) => ea(verify_cons_partial_in(cfg, inner_block, funcdefs, lemmas, prog_vars)),
+            AxStm::For(_, _, _, inner_block) => ea(verify_cons_partial_in(cfg, inner_block, funcdefs, lemmas, prog_vars)),
+            AxStm::Repeat(inner_block, cond) => {
+                ea(verify_cons_partial_in(cfg, inner_block, funcdefs, lemmas, prog_vars));
+
+                let AxBlock(AssertionChain(inner_pre_chain), inner_rem) = inner_block;
+                let inner_pre = inner_pre_chain.first().unwrap();
+                let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
+                let repeat_pre = Bexp::Bop(Box::new(Bexp::Not(Box::new(cond.clone()))), Bopcode::And, Box::new(inner_post.clone()));
+
+                println!("Verifying RepeatAx side-condition (¬b ∧ Q ⊨ P):\n{:?} ⊨ {:?}", repeat_pre, inner_pre);
+
+                ea(check_entailment(cfg, funcdefs, lemmas, prog_vars, &repeat_pre, inner_pre));
+            },
             AxStm::If(_, then_block, else_block) => {
-                ea(verify_cons_partial(cfg, then_block, funcdefs));
-                ea(verify_cons_partial(cfg, else_block, funcdefs));
+                ea(verify_cons_partial_in(cfg, then_block, funcdefs, lemmas, prog_vars));
+                ea(verify_cons_partial_in(cfg, else_block, funcdefs, lemmas, prog_vars));
             },
             _ => (),
         }
-        ea(verify_assertion_chain(cfg, post_chain, funcdefs));
+        ea(verify_assertion_chain(cfg, post_chain, funcdefs, lemmas, prog_vars));
     }
 
     if !errs.0.is_empty() {
@@ -198,6 +318,18 @@ pub fn verify_block_except_cons_total(AxBlock(AssertionChain(first), rem): &AxBl
                     }.into()));
                 }
             },
+            AxStm::ArrAssign(arr, index, aexp) => {
+                // { Q[a ↦ a[i := e]] } a[i] := e { Q }
+                let new_arr = Array::Store(Box::new(Array::Var(arr.clone())), Box::new(index.clone()), Box::new(aexp.clone()));
+                let pre_must = post.clone().substitute_array(arr, &new_arr);
+                if *pre != pre_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: pre.clone(),
+                        expected: pre_must,
+                    }.into()));
+                }
+            },
             AxStm::If(
                 cond,
                 AxBlock(AssertionChain(then_pre_chain), then_rem),
@@ -287,6 +419,63 @@ pub fn verify_block_except_cons_total(AxBlock(AssertionChain(first), rem): &AxBl
                     }.into()))
                 }
             },
+            AxStm::Assert(cond) => {
+                // { b and (Q) } assert b { Q }
+                let pre_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(post.clone()));
+                if *pre != pre_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: pre.clone(),
+                        expected: pre_must,
+                    }.into()))
+                }
+            },
+            AxStm::Assume(cond) => {
+                // { P } assume b { b and (P) }
+                let post_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(pre.clone()));
+                if *post != post_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: post.clone(),
+                        expected: post_must,
+                    }.into()))
+                }
+            },
+            AxStm::Abort => {
+                // { false } abort { Q }
+                let pre_must = Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(1)));
+                if *pre != pre_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: pre.clone(),
+                        expected: pre_must,
+                    }.into()))
+                }
+            },
+            AxStm::Repeat(AxBlock(_, inner_rem), cond) => {
+                // { P } repeat { P and e = Z } s { Q } until b { b and (Q) },
+                // with side-condition not b and (Q) ⊨ P and 0 <= e and e < Z
+                let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
+                let (inner_pre_partial, _, _) = stm.get_while_things();
+
+                if **inner_pre_partial != *pre {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: *inner_pre_partial.clone(),
+                        expected: pre.clone(),
+                    }.into()))
+                }
+                let post_must = Bexp::Bop(Box::new(cond.clone()), Bopcode::And, Box::new(inner_post.clone()));
+                if *post != post_must {
+                    ea(Err(AxStructureError {
+                        stm: stm.clone(),
+                        actual: post.clone(),
+                        expected: post_must,
+                    }.into()))
+                }
+            },
+            // for loops always terminate, since neither the loop variable nor the upper bound may be modified
+            AxStm::For(..) => ea(verify_for_structure(stm, pre, post)),
         }
         pre = post_chain.last().unwrap();
     }
@@ -298,17 +487,22 @@ pub fn verify_block_except_cons_total(AxBlock(AssertionChain(first), rem): &AxBl
     Ok(())
 }
 
-pub fn verify_cons_total(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+pub fn verify_cons_total(cfg: &z3::Config, prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma]) -> Result<()> {
+    verify_cons_total_in(cfg, prog, funcdefs, lemmas, &prog.program_vars())
+}
+
+/// Verifies the entailments of a block of the program, whose variables are `prog_vars`
+fn verify_cons_total_in(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], prog_vars: &HashSet<Var>) -> Result<()> {
     let mut errs = ImpErrors(vec![]);
     let mut ea = |r: Result<()>| {
         err_acc(&mut errs, r);
     };
 
-    ea(verify_assertion_chain(&cfg, first, funcdefs));
+    ea(verify_assertion_chain(&cfg, first, funcdefs, lemmas, prog_vars));
     for (stm, post_chain) in rem {
         match &stm {
             AxStm::While(_, inner_block) => {
-                ea(verify_cons_total(cfg, inner_block, funcdefs));
+                ea(verify_cons_total_in(cfg, inner_block, funcdefs, lemmas, prog_vars));
 
                 let (partial_pre, variant, _) = stm.get_while_things();
 
@@ -316,7 +510,7 @@ pub fn verify_cons_total(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcde
 
                 println!("Verifying WhTotAx side-condition (b ∧ P ⊨ 0 ≤ e):\n{:?} ⊨ {:?}", partial_pre, must_entail);
 
-                ea(check_entailment(cfg, funcdefs, &partial_pre, &must_entail));
+                ea(check_entailment(cfg, funcdefs, lemmas, prog_vars, &partial_pre, &must_entail));
                 // let mut solver = z3::Solver::new(&ctx);
                 // solver.assert(&entails);
                 // let res = solver.check();
@@ -329,12 +523,35 @@ pub fn verify_cons_total(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcde
                 // }
             },
             AxStm::If(_, then_block, else_block) => {
-                ea(verify_cons_total(cfg, then_block, funcdefs));
-                ea(verify_cons_total(cfg, else_block, funcdefs));
+                ea(verify_cons_total_in(cfg, then_block, funcdefs, lemmas, prog_vars));
+                ea(verify_cons_total_in(cfg, else_block, funcdefs, lemmas, prog_vars));
+            },
+            AxStm::For(_, _, _, inner_block) => ea(verify_cons_total_in(cfg, inner_block, funcdefs, lemmas, prog_vars)),
+            AxStm::Repeat(inner_block, cond) => {
+                ea(verify_cons_total_in(cfg, inner_block, funcdefs, lemmas, prog_vars));
+
+                let (partial_pre, variant, logical_var) = stm.get_while_things();
+                let AxBlock(_, inner_rem) = inner_block;
+                let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
+                let repeat_pre = Bexp::Bop(Box::new(Bexp::Not(Box::new(cond.clone()))), Bopcode::And, Box::new(inner_post.clone()));
+
+                let must_entail = Bexp::Bop(
+                    Box::new(Bexp::Bop(
+                        partial_pre.clone(),
+                        Bopcode::And,
+                        Box::new(Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Le, variant.clone())),
+                    )),
+                    Bopcode::And,
+                    Box::new(Bexp::Rop(variant.clone(), Ropcode::Lt, logical_var.clone())),
+                );
+
+                println!("Verifying RepeatTotAx side-condition (¬b ∧ Q ⊨ P ∧ 0 ≤ e ∧ e < Z):\n{:?} ⊨ {:?}", repeat_pre, must_entail);
+
+                ea(check_entailment(cfg, funcdefs, lemmas, prog_vars, &repeat_pre, &must_entail));
             },
             _ => (),
         }
-        ea(verify_assertion_chain(cfg, post_chain, funcdefs));
+        ea(verify_assertion_chain(cfg, post_chain, funcdefs, lemmas, prog_vars));
     }
 
     if !errs.0.is_empty() {
@@ -344,7 +561,85 @@ pub fn verify_cons_total(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcde
     Ok(())
 }
 
-fn verify_assertion_chain(cfg: &z3::Config, AssertionChain(chain): &AssertionChain, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+// { I[x ↦ a1] } for x := a1 to a2 do { x <= a2 and (I) } s { I[x ↦ x + 1] } end { not x <= a2 and (I) },
+// where s may neither assign to x nor to the variables of a2
+fn verify_for_structure(stm: &AxStm, pre: &Bexp, post: &Bexp) -> Result<()> {
+    let mut errs = ImpErrors(vec![]);
+    let mut ea = |r: Result<()>| {
+        err_acc(&mut errs, r);
+    };
+
+    let (v, from, to, inner_block) = match stm {
+        AxStm::For(v, from, to, inner_block) => (v, from, to, inner_block),
+        _ => unreachable!(),
+    };
+    let AxBlock(AssertionChain(inner_pre_chain), inner_rem) = inner_block;
+    let inner_pre = inner_pre_chain.first().unwrap();
+    let inner_post = inner_rem.last().unwrap().1.0.last().unwrap();
+
+    let mut fixed_vars = to.free_vars();
+    fixed_vars.extend(to.free_arrays());
+    fixed_vars.insert(v.clone());
+    if inner_block.modified_vars().iter().any(|modified| fixed_vars.contains(modified)) {
+        ea(Err(Other(format!(
+            "The body of `for {} := {:?} to {:?}` must neither assign to {} nor to the variables of {:?}",
+            v, from, to, v, to
+        )).into()));
+    }
+
+    // The bound is evaluated once before the loop, but the guard below is checked for the updated loop variable
+    if to.free_vars().contains(v) {
+        ea(Err(Other(format!(
+            "The bound {:?} of `for {} := {:?} to {:?}` must not mention the loop variable {}", to, v, from, to, v
+        )).into()));
+    }
+
+    let guard = Bexp::Rop(Box::new(Aexp::Var(v.clone())), Ropcode::Le, Box::new(to.clone()));
+    let invariant = match inner_pre {
+        Bexp::Bop(inner_guard, Bopcode::And, invariant) if **inner_guard == guard => invariant,
+        _ => {
+            ea(Err(Other(format!(
+                "A for loop requires an inner pre-condition of the form {{ {:?} and ( I ) }}, found {{ {:?} }}",
+                guard, inner_pre
+            )).into()));
+            return Err(errs);
+        }
+    };
+
+    let pre_must = invariant.clone().substitute(v, from);
+    if *pre != pre_must {
+        ea(Err(AxStructureError {
+            stm: stm.clone(),
+            actual: pre.clone(),
+            expected: pre_must,
+        }.into()))
+    }
+    let next = Aexp::Op(Box::new(Aexp::Var(v.clone())), Opcode::Add, Box::new(Aexp::Numeral(1)));
+    let inner_post_must = invariant.clone().substitute(v, &next);
+    if *inner_post != inner_post_must {
+        ea(Err(AxStructureError {
+            stm: stm.clone(),
+            actual: inner_post.clone(),
+            expected: inner_post_must,
+        }.into()))
+    }
+    let post_must = Bexp::Bop(Box::new(Bexp::Not(Box::new(guard))), Bopcode::And, invariant.clone());
+    if *post != post_must {
+        ea(Err(AxStructureError {
+            stm: stm.clone(),
+            actual: post.clone(),
+            expected: post_must,
+        }.into()))
+    }
+
+    if !errs.0.is_empty() {
+        return Err(errs);
+    }
+
+    Ok(())
+}
+
+fn verify_assertion_chain(cfg: &z3::Config, AssertionChain(chain): &AssertionChain, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], prog_vars: &HashSet<Var>) -> Result<()> {
     let mut errs = ImpErrors(vec![]);
     let mut ea = |r: Result<()>| {
         err_acc(&mut errs, r);
@@ -356,7 +651,7 @@ fn verify_assertion_chain(cfg: &z3::Config, AssertionChain(chain): &AssertionCha
     for q in chain.iter().skip(1) {
         println!("Verifying ConsAx rule:\n{{ {:?} }} ⊨ {{ {:?} }}", p, q);
 
-        ea(check_entailment(cfg, funcdefs, p, q));
+        ea(check_entailment(cfg, funcdefs, lemmas, prog_vars, p, q));
 
         p = q;
     }
@@ -469,7 +764,7 @@ fn verify_assertion_chain(cfg: &z3::Config, AssertionChain(chain): &AssertionCha
 //     }
 // }
 
-fn check_entailment<'a>(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, p: &Bexp, q: &Bexp) -> Result<()> {
+fn check_entailment<'a>(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], prog_vars: &HashSet<Var>, p: &Bexp, q: &Bexp) -> Result<()> {
     let mut errs = ImpErrors(vec![]);
     let mut ea = |r: Result<()>| {
         err_acc(&mut errs, r);
@@ -480,29 +775,34 @@ fn check_entailment<'a>(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>
 
     // TODO: Configure usage of egg optimizer, disallow on functions
 
-    let (p, q) = if p.can_egg() && q.can_egg() {
-        let p_sexp = p.sexp_string();
-        let q_sexp = q.sexp_string();
-        let p_egg: RecExpr<ImpExpr> = p_sexp.parse().unwrap();
-        let q_egg: RecExpr<ImpExpr> = q_sexp.parse().unwrap();
+    // The premise is canonicalized conjunct by conjunct, so that every conjunct can be tracked in the unsat core
+    let p_conjuncts = p.conjuncts();
+    let (p_conjuncts_canon, q) = if p.can_egg() && q.can_egg() {
+        let mut exprs: Vec<RecExpr<ImpExpr>> = p_conjuncts.iter().map(|conjunct| conjunct.sexp_string().parse().unwrap()).collect();
+        exprs.push(q.sexp_string().parse().unwrap());
 
-        let bests = get_bests(vec![&p_egg, &q_egg]);
+        let bests = get_bests(exprs.iter().collect());
         println!("Found bests: {}", bests.iter().map(|recexpr| recexpr.to_string()).collect::<Vec<_>>().join(", "));
 
-        let p_canon_sexp = bests[0].to_string();
-        let q_canon_sexp = bests[1].to_string();
-
-        let p_canon: Bexp = *SBexpParser::new().parse(p_canon_sexp.as_str()).unwrap();
-        let q_canon: Bexp = *SBexpParser::new().parse(q_canon_sexp.as_str()).unwrap();
-        (p_canon, q_canon)
+        let mut canon: Vec<Bexp> = bests.iter()
+            .map(|best| *SBexpParser::new().parse(best.to_string().as_str()).unwrap())
+            .collect();
+        let q_canon = canon.pop().unwrap();
+        (canon, q_canon)
     } else {
-        (p.clone(), q.clone())
+        (p_conjuncts.iter().map(|&conjunct| conjunct.clone()).collect(), q.clone())
     };
+    let p = p_conjuncts_canon.iter().skip(1).cloned().fold(p_conjuncts_canon[0].clone(), |acc, conjunct| {
+        Bexp::Bop(Box::new(acc), Bopcode::And, Box::new(conjunct))
+    });
 
     let funcmap = build_funcmap(&ctx, funcdefs)?;
 
     let p_entails_q = entails(&ctx, p.to_z3_bool(&ctx, &funcmap), q.to_z3_bool(&ctx, &funcmap));
-    let mut solver = z3::Solver::new(&ctx);
+    let solver = z3::Solver::new(&ctx);
+    for lemma in lemmas {
+        solver.assert(&lemma.to_z3_axiom(&ctx, &funcmap));
+    }
     solver.assert(&p_entails_q);
     // let x = z3::ast::Int::new_const(&ctx, "x");
     // let x_minus_1 = z3::ast::Int::sub(&ctx, &[&x, &z3::ast::Int::from_i64(&ctx, 1)]);
@@ -517,45 +817,207 @@ fn check_entailment<'a>(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>
     let res = solver.check();
     if res == SatResult::Unsat {
         println!("Verified.");
-    } else if res == SatResult::Unknown {
+
+        if let Some(needed) = needed_conjuncts(&ctx, &funcmap, lemmas, &p_conjuncts_canon, &q) {
+            let (needed, unneeded): (Vec<(&Bexp, bool)>, Vec<_>) = p_conjuncts.iter()
+                .copied()
+                .zip(needed)
+                .partition(|(_, is_needed)| *is_needed);
+            if !unneeded.is_empty() {
+                let string_of_conjuncts = |conjuncts: Vec<(&Bexp, bool)>| {
+                    conjuncts.iter().map(|(conjunct, _)| format!("{{ {:?} }}", conjunct)).collect::<Vec<_>>().join(", ")
+                };
+                if needed.is_empty() {
+                    println!("The conclusion holds without any conjunct of the premise.");
+                } else {
+                    println!("The conclusion only needs these conjuncts of the premise: {}", string_of_conjuncts(needed));
+                }
+                println!("The following conjuncts are not needed: {}", string_of_conjuncts(unneeded));
+            }
+        }
+    } else {
+        // Every conjunct of the conclusion is checked separately, so that only the ones not following are reported
+        let q_conjuncts = q.conjuncts();
+        if q_conjuncts.len() == 1 {
+            ea(refute_entailment(&ctx, &funcmap, &solver, res, prog_vars, &p, &q));
+        } else {
+            println!("Checking the conjuncts of the conclusion separately...");
+            for q_conjunct in q_conjuncts {
+                let solver = z3::Solver::new(&ctx);
+                for lemma in lemmas {
+                    solver.assert(&lemma.to_z3_axiom(&ctx, &funcmap));
+                }
+                solver.assert(&entails(&ctx, p.to_z3_bool(&ctx, &funcmap), q_conjunct.to_z3_bool(&ctx, &funcmap)));
+
+                println!("{{ {:?} }}:", q_conjunct);
+                let res = solver.check();
+                if res == SatResult::Unsat {
+                    println!("Verified.");
+                } else {
+                    ea(refute_entailment(&ctx, &funcmap, &solver, res, prog_vars, &p, q_conjunct));
+                }
+            }
+        }
+    }
+
+    if !errs.0.is_empty() {
+        return Err(errs);
+    }
+
+    Ok(())
+}
+
+/// The error for the entailment `p |= q`, whose negation the solver found satisfiable or could not decide
+fn refute_entailment<'a>(ctx: &'a z3::Context, funcmap: &HashMap<String, RecFuncDecl<'a>>, solver: &z3::Solver<'a>, res: SatResult, prog_vars: &HashSet<Var>, p: &Bexp, q: &Bexp) -> Result<()> {
+    if res == SatResult::Unknown {
         println!("ERROR! Couldn't prove or disprove. Unknown.");
-        ea(Err(EntailmentError {
+        return Err(EntailmentError {
             entailment_src: p.clone(),
             entailment_dst: q.clone(),
             is_unknown: true,
             untrue_model: None,
-        }.into()));
-    } else {
-        println!("ERROR {:?}!", res);
-        println!("Model where entailment does not hold:\n{:?}", solver.get_model().unwrap());
+            program_vars: prog_vars.clone(),
+            src_conjuncts: vec![],
+            dst_conjuncts: vec![],
+        }.into());
+    }
 
-        let mut fv = p.free_vars();
-        fv.extend(q.free_vars());
+    println!("ERROR {:?}!", res);
 
-        // panic!("verification failed.");
-        // panic!(format!("ERROR Result is {:?}", res));
+    let mut fv = p.free_vars();
+    fv.extend(q.free_vars());
 
-        ea(Err(EntailmentError {
-            entailment_src: p.clone(),
-            entailment_dst: q.clone(),
-            is_unknown: false,
-            untrue_model: Some(map_of_model(&ctx, solver.get_model().unwrap(), fv)),
-        }.into()));
+    let model = minimized_model(ctx, solver, &fv);
+    let conjunct_values = |bexp: &Bexp| -> Vec<(Bexp, Option<bool>)> {
+        bexp.conjuncts().into_iter()
+            .map(|conjunct| (conjunct.clone(), model.eval(&conjunct.to_z3_bool(ctx, funcmap), true).and_then(|value| value.as_bool())))
+            .collect()
+    };
+    let (src_conjuncts, dst_conjuncts) = (conjunct_values(p), conjunct_values(q));
+
+    Err(EntailmentError {
+        entailment_src: p.clone(),
+        entailment_dst: q.clone(),
+        is_unknown: false,
+        untrue_model: Some(map_of_model(ctx, model, fv)),
+        program_vars: prog_vars.clone(),
+        src_conjuncts,
+        dst_conjuncts,
+    }.into())
+}
+
+/// The timeout of each check when minimizing an unsat core, which is short since most checks fail
+const MINIMIZE_TIMEOUT_MSEC: u32 = 500;
+
+/// Which conjuncts of the premise are needed to prove the valid entailment `conjuncts |= q`, if Z3 finds an unsat core.
+/// Every conjunct is only assumed if its tracker is, so the trackers in the unsat core correspond to the needed ones.
+/// Assumptions prevent some simplifications of Z3, hence this is a separate query after the entailment is verified.
+fn needed_conjuncts<'a>(ctx: &'a z3::Context, funcmap: &HashMap<String, RecFuncDecl<'a>>, lemmas: &[ImpLemma], conjuncts: &[Bexp], q: &Bexp) -> Option<Vec<bool>> {
+    let solver = z3::Solver::new(ctx);
+    for lemma in lemmas {
+        solver.assert(&lemma.to_z3_axiom(ctx, funcmap));
     }
 
-    if !errs.0.is_empty() {
-        return Err(errs);
+    let trackers: Vec<z3::ast::Bool> = (0..conjuncts.len())
+        .map(|i| z3::ast::Bool::new_const(ctx, format!("premise!{}", i)))
+        .collect();
+    for (tracker, conjunct) in trackers.iter().zip(conjuncts) {
+        solver.assert(&tracker.implies(&conjunct.to_z3_bool(ctx, funcmap)));
     }
+    solver.assert(&q.to_z3_bool(ctx, funcmap).not());
 
-    Ok(())
+    if solver.check_assumptions(&trackers) != SatResult::Unsat {
+        return None;
+    }
+    // The core is minimized by dropping the conjuncts that are not needed one by one, since Z3's own minimization
+    // (core.minimize) ignores the timeout and may not terminate with lemmas about recursive functions. A conjunct
+    // is only dropped if Z3 proves the entailment without it within MINIMIZE_TIMEOUT_MSEC.
+    let mut params = z3::Params::new(ctx);
+    params.set_u32("timeout", MINIMIZE_TIMEOUT_MSEC);
+    solver.set_params(&params);
+    let mut core = solver.get_unsat_core();
+    let mut i = 0;
+    while i < core.len() {
+        let mut smaller = core.clone();
+        smaller.remove(i);
+        if solver.check_assumptions(&smaller) == SatResult::Unsat {
+            core = smaller;
+        } else {
+            i += 1;
+        }
+    }
+    Some(trackers.iter().map(|tracker| core.contains(tracker)).collect())
 }
 
-fn map_of_model(ctx: &z3::Context, model: Model, fv: HashSet<Var>) -> HashMap<String, i64> {
+/// A model of the assertions of the solver with small absolute values. First the largest absolute value of the
+/// variables `fv` is minimized, then the absolute value of each variable in alphabetical order, by binary search.
+pub(crate) fn minimized_model<'a>(ctx: &'a z3::Context, solver: &z3::Solver<'a>, fv: &HashSet<Var>) -> Model<'a> {
+    let mut vars: Vec<&Var> = fv.iter().collect();
+    vars.sort();
+    let consts: Vec<z3::ast::Int> = vars.iter().map(|var| z3::ast::Int::new_const(ctx, var.as_str())).collect();
+
+    let max_abs = |model: &Model<'a>, group: &[usize]| -> i64 {
+        group.iter()
+            .map(|&i| model.eval(&consts[i], true).and_then(|val| val.as_i64()).unwrap_or(0).saturating_abs())
+            .max()
+            .unwrap_or(0)
+    };
+    let bound = |group: &[usize], bound: i64| {
+        for &i in group {
+            solver.assert(&consts[i].le(&z3::ast::Int::from_i64(ctx, bound)));
+            solver.assert(&consts[i].ge(&z3::ast::Int::from_i64(ctx, -bound)));
+        }
+    };
+
+    let mut model = solver.get_model().unwrap();
+    let mut groups: Vec<Vec<usize>> = vec![(0..consts.len()).collect()];
+    groups.extend((0..consts.len()).map(|i| vec![i]));
+
+    solver.push();
+    for group in groups {
+        // The smallest bound with a model lies in `lo..=hi`
+        let (mut lo, mut hi) = (0, max_abs(&model, &group));
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            solver.push();
+            bound(&group, mid);
+            // A timeout is treated like no model, since `model` is within the bound `hi` anyway
+            if solver.check() == SatResult::Sat {
+                model = solver.get_model().unwrap();
+                hi = max_abs(&model, &group);
+            } else {
+                lo = mid + 1;
+            }
+            solver.pop(1);
+        }
+        bound(&group, hi);
+    }
+    solver.pop(1);
+
+    model
+}
+
+pub(crate) fn map_of_model(ctx: &z3::Context, model: Model, fv: HashSet<Var>) -> HashMap<String, i64> {
     fv.into_iter().map(|v| {
         (v.clone(), model.eval(&z3::ast::Int::new_const(ctx, v.as_str()), true).unwrap().as_i64().unwrap())
     }).collect()
 }
 
+/// The state of the model, with the free variables `fv` and the array entries `reads`, see `Aexp::array_reads`
+pub(crate) fn state_of_model<'a>(ctx: &'a z3::Context, funcmap: &HashMap<String, RecFuncDecl<'a>>, model: Model<'a>, fv: HashSet<Var>, reads: Vec<(Var, Aexp)>) -> State {
+    let mut state = State::new();
+    for (a, index) in reads {
+        let arr = Array::Var(a.clone()).to_z3_array(ctx, funcmap);
+        let index = model.eval(&index.to_z3_int(ctx, funcmap), true).unwrap();
+        let val = model.eval(&arr.select(&index).as_int().unwrap(), true).unwrap();
+        state.update_array(&a, index.as_i64().unwrap(), val.as_i64().unwrap());
+    }
+    for (v, val) in map_of_model(ctx, model, fv) {
+        state.update(&v, val);
+    }
+    state
+}
+
 fn entails<'a>(ctx: &'a z3::Context, a: z3::ast::Bool<'a>, b: z3::ast::Bool<'a>) -> z3::ast::Bool<'a> {
     z3::ast::Bool::and(ctx, &[&a, &b.not()])
 }
\ No newline at end of file
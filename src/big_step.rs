@@ -1,39 +1,95 @@
 use super::ast::*;
 use super::state::*;
 use super::expression::{arithmetic_eval, boolean_eval};
+use super::Result;
+use std::collections::HashMap;
 
-pub fn run(initial: Configuration) -> State {
+/// Runs `initial` to completion. The resulting configuration is `Terminal` for a normal execution,
+/// otherwise it is the `Failed`, `Blocked` or `Stuck` configuration the execution ended in.
+pub fn run(initial: Configuration, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<Configuration> {
     let (stm, mut initial_state) = match initial {
-        Configuration::Terminal(s) => return s,
         Configuration::Nonterminal(stm, s) => (stm, s),
+        other => return Ok(other),
     };
 
     match *stm.clone() {
-        Stm::Skip => initial_state,
+        Stm::Skip => Ok(Configuration::Terminal(initial_state)),
         Stm::Assign(x, e) => {
-            initial_state.update(&x, arithmetic_eval(&e, &initial_state));
-            initial_state
+            initial_state.update(&x, arithmetic_eval(&e, &initial_state, funcdefs)?);
+            Ok(Configuration::Terminal(initial_state))
+        },
+        Stm::ArrAssign(a, i, e) => {
+            let index = arithmetic_eval(&i, &initial_state, funcdefs)?;
+            initial_state.update_array(&a, index, arithmetic_eval(&e, &initial_state, funcdefs)?);
+            Ok(Configuration::Terminal(initial_state))
         },
         Stm::Seq(stm1, stm2) => {
-            let state1 = run(Configuration::Nonterminal(stm1, initial_state));
-            run(Configuration::Nonterminal(stm2, state1))
+            match run(Configuration::Nonterminal(stm1, initial_state), funcdefs)? {
+                Configuration::Terminal(state1) => run(Configuration::Nonterminal(stm2, state1), funcdefs),
+                other => Ok(other),
+            }
         },
         Stm::If(cond, stm_then, stm_else) => {
-            if boolean_eval(&cond, &initial_state) {
-                run(Configuration::Nonterminal(stm_then, initial_state))
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                run(Configuration::Nonterminal(stm_then, initial_state), funcdefs)
             } else {
-                run(Configuration::Nonterminal(stm_else, initial_state))
+                run(Configuration::Nonterminal(stm_else, initial_state), funcdefs)
             }
         },
         Stm::While(cond, stm_inner) => {
-            if boolean_eval(&cond, &initial_state) {
-                let state1 = run(Configuration::Nonterminal(stm_inner, initial_state));
-
-                run(Configuration::Nonterminal(stm, state1))
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                match run(Configuration::Nonterminal(stm_inner, initial_state), funcdefs)? {
+                    Configuration::Terminal(state1) => run(Configuration::Nonterminal(stm, state1), funcdefs),
+                    other => Ok(other),
+                }
+            } else {
+                Ok(Configuration::Terminal(initial_state))
+            }
+        },
+        Stm::Assert(cond) => {
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                Ok(Configuration::Terminal(initial_state))
             } else {
-                initial_state
+                Ok(Configuration::Failed(*cond, initial_state))
             }
-        }
+        },
+        Stm::Assume(cond) => {
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                Ok(Configuration::Terminal(initial_state))
+            } else {
+                Ok(Configuration::Blocked(*cond, initial_state))
+            }
+        },
+        // There is no derivation for abort
+        Stm::Abort => Ok(Configuration::Stuck(stm, initial_state)),
+        Stm::Repeat(stm_inner, cond) => {
+            match run(Configuration::Nonterminal(stm_inner, initial_state), funcdefs)? {
+                Configuration::Terminal(state1) => {
+                    if boolean_eval(&cond, &state1, funcdefs)? {
+                        Ok(Configuration::Terminal(state1))
+                    } else {
+                        run(Configuration::Nonterminal(stm, state1), funcdefs)
+                    }
+                },
+                other => Ok(other),
+            }
+        },
+        Stm::For(x, from, to, stm_inner) => {
+            // Both bounds are evaluated once, before the first iteration
+            let from = arithmetic_eval(&from, &initial_state, funcdefs)?;
+            let to = arithmetic_eval(&to, &initial_state, funcdefs)?;
+            initial_state.update(&x, from);
 
+            if from <= to {
+                match run(Configuration::Nonterminal(stm_inner.clone(), initial_state), funcdefs)? {
+                    Configuration::Terminal(state1) => {
+                        run(Configuration::Nonterminal(Stm::next_for_iteration(x, to, stm_inner), state1), funcdefs)
+                    },
+                    other => Ok(other),
+                }
+            } else {
+                Ok(Configuration::Terminal(initial_state))
+            }
+        },
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,97 @@
+use super::ast::*;
+use super::axiomatic::{build_funcmap, state_of_model};
+use super::contracts::is_executable;
+use super::expression::boolean_eval;
+use super::error::{string_of_model, ImpErrors};
+use super::error::ImpErrorInner::*;
+use super::small_step::SOS;
+use super::state::{Configuration, State};
+use super::symbolic::{explore, PathEnd, SymPath};
+use std::collections::{HashMap, HashSet};
+use z3::ast::Bool;
+use z3::SatResult;
+
+use super::Result;
+
+/// The condition on the initial values under which the path violates `{pre} stm {post}`
+fn violation(path: &SymPath, post: &Bexp) -> Option<Bexp> {
+    let condition = path.condition.iter().cloned()
+        .fold(Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0))), |acc, cond| {
+            Bexp::Bop(Box::new(acc), Bopcode::And, Box::new(cond))
+        });
+
+    match &path.end {
+        PathEnd::Terminal => Some(Bexp::Bop(
+            Box::new(condition), Bopcode::And, Box::new(Bexp::Not(Box::new(path.at_end(post))))
+        )),
+        PathEnd::Failed(_) => Some(condition),
+        // Partial correctness holds trivially for executions that abort
+        PathEnd::Stuck | PathEnd::Unfinished => None,
+    }
+}
+
+/// Runs the counterexample with the small-step semantics, printing the trace, and returns the violation
+fn replay(stm: &Stm, funcdefs: &HashMap<String, ImpFuncDef>, post: &Bexp, initial: State) -> ImpErrors {
+    let not_violated = |initial: &State| Other(format!(
+        "Z3 found the counterexample {}, but running the program from it does not violate the postcondition",
+        string_of_model(&initial.entries())
+    )).into();
+
+    println!("Counterexample trace:");
+    let mut sos = SOS::new(Configuration::Nonterminal(Box::new(stm.clone()), initial.clone()), funcdefs.clone());
+    let (assertion, state) = match sos.run_execution() {
+        Ok(Configuration::Terminal(state)) => {
+            let holds = is_executable(post) && matches!(boolean_eval(&Box::new(post.clone()), &state, funcdefs), Ok(true));
+            if holds {
+                return not_violated(&initial);
+            }
+            (post.clone(), state)
+        },
+        Ok(Configuration::Failed(assertion, state)) => (assertion, state),
+        Ok(_) => return not_violated(&initial),
+        Err(errs) => return errs,
+    };
+
+    AssertionViolation { assertion, initial: initial.entries(), state: state.entries() }.into()
+}
+
+/// An initial state satisfying `pre` from which one of the paths violates `post` or an `assert`. The paths are combined
+/// into one formula relating the initial values to the final values, so a single query to Z3 suffices.
+pub(crate) fn find_counterexample(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, pre: &Bexp, paths: &[SymPath], post: &Bexp) -> Result<Option<State>> {
+    let violations: Vec<Bexp> = paths.iter().filter_map(|path| violation(path, post)).collect();
+
+    let mut vars: HashSet<Var> = pre.free_vars();
+    vars.extend(violations.iter().flat_map(|bexp| bexp.free_vars()));
+    let mut reads = pre.array_reads();
+    reads.extend(violations.iter().flat_map(|bexp| bexp.array_reads()));
+
+    let ctx = z3::Context::new(cfg);
+    let funcmap = build_funcmap(&ctx, funcdefs)?;
+    let solver = z3::Solver::new(&ctx);
+    solver.assert(&pre.to_z3_bool(&ctx, &funcmap));
+    let z3_violations: Vec<Bool> = violations.iter().map(|bexp| bexp.to_z3_bool(&ctx, &funcmap)).collect();
+    solver.assert(&Bool::or(&ctx, &z3_violations.iter().collect::<Vec<_>>()));
+
+    match solver.check() {
+        SatResult::Unsat => Ok(None),
+        SatResult::Unknown => Err(Other(format!(
+            "Z3 could not decide whether the program satisfies {{ {:?} }} ... {{ {:?} }}", pre, post
+        )).into()),
+        SatResult::Sat => Ok(Some(state_of_model(&ctx, &funcmap, solver.get_model().unwrap(), vars, reads))),
+    }
+}
+
+/// Checks `{pre} stm {post}` for partial correctness, for all executions in which every loop runs at most
+/// `max_iterations` times. A counterexample is replayed with the small-step semantics.
+/// Returns whether the check is complete, i.e. no execution exceeds `max_iterations` iterations.
+pub fn check_bounded(cfg: &z3::Config, prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, max_iterations: usize) -> Result<bool> {
+    let (pre, stm, post) = prog.spec();
+
+    let paths = explore(cfg, &stm, funcdefs, max_iterations)?;
+    let complete = paths.iter().all(|path| !matches!(path.end, PathEnd::Unfinished));
+
+    match find_counterexample(cfg, funcdefs, &pre, &paths, &post)? {
+        None => Ok(complete),
+        Some(input) => Err(replay(&stm, funcdefs, &post, input)),
+    }
+}
@@ -0,0 +1,258 @@
+use super::ast::*;
+use super::axiomatic::{build_funcmap, map_of_model};
+use super::big_step;
+use super::bmc::find_counterexample;
+use super::contracts::is_executable;
+use super::error::{string_of_model, ImpErrors};
+use super::expression::boolean_eval;
+use super::state::{Configuration, State};
+use super::symbolic::{explore, PathEnd};
+use std::collections::HashMap;
+use z3::SatResult;
+
+use super::Result;
+
+/// Loops of the statement before an entailment are unrolled at most this many times when searching for a state before it
+const MAX_ITERATIONS: usize = 5;
+
+/// What running the statement before a failed entailment `p |= q` from a state satisfying its precondition shows
+enum Diagnosis {
+    /// The initial state, the final state violating `q`, and whether it is the counterexample of Z3 itself
+    Reached(State, State, bool),
+    /// No execution ends in a state violating `q`, so `p` is weaker than needed
+    TooWeak,
+    Inconclusive(String),
+}
+
+/// A model of `p and not q`, if Z3 finds one
+fn refuting_model(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], p: &Bexp, q: &Bexp) -> Result<Option<HashMap<String, i64>>> {
+    let ctx = z3::Context::new(cfg);
+    let funcmap = build_funcmap(&ctx, funcdefs)?;
+    let solver = z3::Solver::new(&ctx);
+    for lemma in lemmas {
+        solver.assert(&lemma.to_z3_axiom(&ctx, &funcmap));
+    }
+    solver.assert(&p.to_z3_bool(&ctx, &funcmap));
+    solver.assert(&q.to_z3_bool(&ctx, &funcmap).not());
+
+    if solver.check() != SatResult::Sat {
+        return Ok(None);
+    }
+
+    let mut fv = p.free_vars();
+    fv.extend(q.free_vars());
+    Ok(Some(map_of_model(&ctx, solver.get_model().unwrap(), fv)))
+}
+
+fn string_of_errors(errs: &ImpErrors) -> String {
+    errs.0.iter().map(|err| err.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+/// `x = v and ...` for every variable `x` with value `v` in the model
+fn equals_model(model: &HashMap<String, i64>) -> Bexp {
+    let mut vars: Vec<(&String, &i64)> = model.iter().collect();
+    vars.sort();
+
+    vars.into_iter()
+        .map(|(var, val)| Bexp::Rop(Box::new(Aexp::Var(var.clone())), Ropcode::Eq, Box::new(Aexp::Numeral(*val))))
+        .fold(Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0))), |acc, eq| {
+            Bexp::Bop(Box::new(acc), Bopcode::And, Box::new(eq))
+        })
+}
+
+/// The executions before a chain, which start in a state satisfying the precondition of the statement
+enum Prefix<'a> {
+    /// The chain follows the statement
+    After(&'a Stm),
+    /// The chain starts the block, which is nested in the program
+    Entry(&'a AxBlock, &'a Program<'a>),
+}
+
+/// Builds the program before the end of the statement containing a block from a statement replacing the block
+type Program<'a> = dyn Fn(Box<Stm>) -> Stm + 'a;
+
+impl Prefix<'_> {
+    /// The statement run from the precondition, which fails `assert t` at the start of the chain iff it reaches a
+    /// state violating `t` there, and the postcondition that its final states violate iff they violate `t`
+    fn reaching(&self, t: &Bexp) -> (Stm, Bexp) {
+        match self {
+            Prefix::After(stm) => ((*stm).clone(), t.clone()),
+            Prefix::Entry(block, program) => {
+                let assert = Box::new(Stm::Assert(Box::new(t.clone())));
+                let always = Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0)));
+                (program(Box::new(Stm::Seq(assert, (*block).clone().into_stm()))), always)
+            },
+        }
+    }
+
+    /// The state before the chain, if the execution of the statement from `reaching(t)` ends in one violating `t`
+    fn state_before<'c>(&self, t: &Bexp, config: &'c Configuration) -> Option<&'c State> {
+        match (self, config) {
+            (Prefix::After(_), Configuration::Terminal(state)) => Some(state),
+            (Prefix::Entry(..), Configuration::Failed(assertion, state)) if assertion == t => Some(state),
+            _ => None,
+        }
+    }
+
+    fn stm(&self) -> Stm {
+        match self {
+            Prefix::After(stm) => (*stm).clone(),
+            Prefix::Entry(block, program) => program((*block).clone().into_stm()),
+        }
+    }
+
+    /// How the execution of the statement gets to the chain
+    fn reaches(&self) -> &'static str {
+        match self {
+            Prefix::After(_) => "ends in",
+            Prefix::Entry(..) => "reaches the start of the block in",
+        }
+    }
+}
+
+/// Searches for a state satisfying `pre` from which the prefix reaches the model, or else any state violating `q`,
+/// and runs it from there with the big-step semantics
+fn diagnose(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, pre: &Bexp, prefix: &Prefix, q: &Bexp, model: &HashMap<String, i64>) -> Result<Diagnosis> {
+    let mut complete = true;
+
+    // A path violates `not (x = v and ...)` iff it reaches the model
+    let targets = vec![(Bexp::Not(Box::new(equals_model(model))), true), (q.clone(), false)];
+    for (target, is_model) in targets {
+        let (stm, post) = prefix.reaching(&target);
+        let paths = explore(cfg, &stm, funcdefs, MAX_ITERATIONS)?;
+        complete &= paths.iter().all(|path| !matches!(path.end, PathEnd::Unfinished));
+
+        let initial = match find_counterexample(cfg, funcdefs, pre, &paths, &post) {
+            Ok(Some(initial)) => initial,
+            Ok(None) => continue,
+            Err(_) => return Ok(Diagnosis::Inconclusive("Z3 could not decide whether the statement reaches it.".to_owned())),
+        };
+
+        let config = match big_step::run(Configuration::Nonterminal(Box::new(stm), initial.clone()), funcdefs) {
+            Ok(config) => config,
+            Err(errs) => return Ok(Diagnosis::Inconclusive(format!(
+                "Running the statement from {} fails: {}", string_of_model(&initial.entries()), string_of_errors(&errs)
+            ))),
+        };
+        return match prefix.state_before(&target, &config) {
+            Some(state) => {
+                let holds = if is_executable(q) { boolean_eval(&Box::new(q.clone()), state, funcdefs) } else { Ok(false) };
+                match holds {
+                    Ok(true) => Ok(Diagnosis::Inconclusive(format!(
+                        "Running the statement from {} {} {}, where the assertion holds after all.",
+                        string_of_model(&initial.entries()), prefix.reaches(), string_of_model(&state.entries())
+                    ))),
+                    Ok(false) => Ok(Diagnosis::Reached(initial, state.clone(), is_model)),
+                    Err(errs) => Ok(Diagnosis::Inconclusive(format!(
+                        "Running the statement from {} {} {}, where the assertion cannot be evaluated: {}",
+                        string_of_model(&initial.entries()), prefix.reaches(), string_of_model(&state.entries()), string_of_errors(&errs)
+                    ))),
+                }
+            },
+            None => Ok(Diagnosis::Inconclusive(format!(
+                "Running the statement from {} does not terminate normally: {:?}", string_of_model(&initial.entries()), config
+            ))),
+        };
+    }
+
+    if complete {
+        Ok(Diagnosis::TooWeak)
+    } else {
+        Ok(Diagnosis::Inconclusive(format!(
+            "No execution with at most {} loop iterations {} a state violating the assertion.", MAX_ITERATIONS, prefix.reaches()
+        )))
+    }
+}
+
+/// Concretizes the counterexamples of the failing entailments of a chain, which the prefix reaches from precondition `pre`
+fn concretize_chain(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], pre: &Bexp, prefix: &Prefix, AssertionChain(chain): &AssertionChain) -> Result<()> {
+    let stm = prefix.stm();
+    for (p, q) in chain.iter().zip(chain.iter().skip(1)) {
+        let model = match refuting_model(cfg, funcdefs, lemmas, p, q)? {
+            Some(model) => model,
+            None => continue,
+        };
+
+        println!("Concretizing the counterexample {} to the entailment:\n{{ {:?} }} ⊨ {{ {:?} }}", string_of_model(&model), p, q);
+        println!("Searching for a state before {:?} satisfying {{ {:?} }}...", stm, pre);
+        match diagnose(cfg, funcdefs, pre, prefix, q, &model)? {
+            Diagnosis::Reached(initial, state, is_model) => {
+                if is_model {
+                    println!("Running the statement from {} {} the counterexample {}.", string_of_model(&initial.entries()), prefix.reaches(), string_of_model(&state.entries()));
+                } else {
+                    println!("The counterexample cannot be reached, but running the statement from {} {} {}, where {{ {:?} }} does not hold.",
+                             string_of_model(&initial.entries()), prefix.reaches(), string_of_model(&state.entries()), q);
+                }
+                match prefix {
+                    Prefix::After(_) => println!("The outline is wrong: {{ {:?} }} {:?} {{ {:?} }} does not hold.\n", pre, stm, q),
+                    Prefix::Entry(..) => println!("The outline is wrong: {{ {:?} }} does not hold at the start of the block in every execution of {:?} from {{ {:?} }}.\n", q, stm, pre),
+                }
+            },
+            Diagnosis::TooWeak => {
+                println!("The counterexample cannot be reached, and no execution {} a state where {{ {:?} }} does not hold.", prefix.reaches(), q);
+                println!("The assertion {{ {:?} }} is weaker than needed, the entailment holds for every state the statement reaches.\n", p);
+            },
+            Diagnosis::Inconclusive(msg) => println!("{}\n", msg),
+        }
+    }
+
+    Ok(())
+}
+
+/// The statement with its then branch or body replaced by `inner` if `is_first`, or else its else branch
+fn with_block(stm: &AxStm, is_first: bool, inner: Box<Stm>) -> Stm {
+    match (stm, is_first) {
+        (AxStm::If(cond, _, else_block), true) => Stm::If(Box::new(cond.clone()), inner, else_block.clone().into_stm()),
+        (AxStm::If(cond, then_block, _), false) => Stm::If(Box::new(cond.clone()), then_block.clone().into_stm(), inner),
+        (AxStm::While(cond, _), _) => Stm::While(Box::new(cond.clone()), inner),
+        (AxStm::Repeat(_, cond), _) => Stm::Repeat(inner, Box::new(cond.clone())),
+        (AxStm::For(v, from, to, _), _) => Stm::For(v.clone(), Box::new(from.clone()), Box::new(to.clone()), inner),
+        (stm, _) => *stm.clone().into_stm(),
+    }
+}
+
+/// For every ConsAx entailment `p |= q` of the outline that does not hold, maps the counterexample of Z3 back to a
+/// state before the statement preceding the entailment, and runs the statement from it with the big-step semantics.
+/// This shows whether the outline is actually wrong or the counterexample cannot be reached and `p` is too weak.
+/// The chain at the start of the outline has no statement before it, so its first assertion is checked against `q`.
+/// The chain at the start of a branch or loop body is reached by running the program from its precondition.
+pub fn concretize_entailments(cfg: &z3::Config, prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma]) -> Result<()> {
+    concretize_block(cfg, prog, funcdefs, lemmas, prog.pre(), &Prefix::After(&Stm::Skip), None)
+}
+
+/// Concretizes the entailments of the block, whose first chain the prefix reaches from precondition `pre`. The
+/// block is nested in the program with the precondition and the function of `Prefix::Entry` in `outer`, if any.
+fn concretize_block(cfg: &z3::Config, AxBlock(first, rem): &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma], pre: &Bexp, prefix: &Prefix, outer: Option<(&Bexp, &Program)>) -> Result<()> {
+    concretize_chain(cfg, funcdefs, lemmas, pre, prefix, first)?;
+
+    let program_pre = outer.map_or(first.0.first().unwrap(), |(program_pre, _)| program_pre);
+    let mut pre = first.0.last().unwrap();
+    for (i, (stm, post_chain)) in rem.iter().enumerate() {
+        let inner_blocks = match stm {
+            AxStm::If(_, then_block, else_block) => vec![(then_block, true), (else_block, false)],
+            AxStm::While(_, inner_block) | AxStm::Repeat(inner_block, _) | AxStm::For(_, _, _, inner_block) => vec![(inner_block, true)],
+            _ => vec![],
+        };
+        for (inner_block, is_first) in inner_blocks {
+            // The statements after this one only matter if the block is repeated by an enclosing loop
+            let program = |inner: Box<Stm>| {
+                let after = if outer.is_some() { &rem[i + 1..] } else { &[] };
+                let block = rem[..i].iter().map(|(stm, _)| stm.clone().into_stm())
+                    .chain(std::iter::once(Box::new(with_block(stm, is_first, inner))))
+                    .chain(after.iter().map(|(stm, _)| stm.clone().into_stm()))
+                    .reduce(|acc, stm| Box::new(Stm::Seq(acc, stm)))
+                    .unwrap();
+                match outer {
+                    Some((_, outer_program)) => outer_program(block),
+                    None => *block,
+                }
+            };
+            let prefix = Prefix::Entry(inner_block, &program);
+            concretize_block(cfg, inner_block, funcdefs, lemmas, program_pre, &prefix, Some((program_pre, &program)))?;
+        }
+        concretize_chain(cfg, funcdefs, lemmas, pre, &Prefix::After(&stm.clone().into_stm()), post_chain)?;
+        pre = post_chain.0.last().unwrap();
+    }
+
+    Ok(())
+}
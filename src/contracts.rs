@@ -0,0 +1,223 @@
+use super::ast::*;
+use super::error::ImpErrorInner::*;
+use super::expression::{boolean_eval, quantifier_bounds};
+use super::sampling::{Sampler, MAX_STEPS, MAX_TRIES, SAMPLES, SAMPLE_BOUND};
+use super::small_step::SOS;
+use super::state::{Configuration, State};
+use std::collections::{HashMap, HashSet};
+
+use super::Result;
+
+/// Only quantifiers whose variable is bounded can be evaluated, hence assertions containing other quantifiers are
+/// not checked at runtime
+pub(crate) fn is_executable(bexp: &Bexp) -> bool {
+    match bexp {
+        Bexp::Rop(_, _, _) | Bexp::PredApp(_, _) => true,
+        Bexp::Bop(left, _, right) => is_executable(left) && is_executable(right),
+        Bexp::Not(inner) => is_executable(inner),
+        Bexp::Quant(quantifier, x, body) => quantifier_bounds(quantifier, x, body).is_some() && is_executable(body),
+    }
+}
+
+/// The number of assertions of the block that are not checked at runtime, see `is_executable`
+pub fn unchecked_assertions(block: &AxBlock) -> usize {
+    let AxBlock(first, rem) = block;
+    let unchecked = |chain: &AssertionChain| chain.0.iter().filter(|assertion| !is_executable(assertion)).count();
+
+    rem.iter().fold(unchecked(first), |res, (stm, chain)| {
+        res + unchecked(chain) + match stm {
+            AxStm::If(_, then_block, else_block) => unchecked_assertions(then_block) + unchecked_assertions(else_block),
+            AxStm::While(_, inner_block) | AxStm::Repeat(inner_block, _) | AxStm::For(_, _, _, inner_block) => {
+                unchecked_assertions(inner_block)
+            },
+            _ => 0,
+        }
+    })
+}
+
+fn seq(first: Box<Stm>, second: Box<Stm>) -> Box<Stm> {
+    match *first {
+        Stm::Skip => second,
+        _ => Box::new(Stm::Seq(first, second)),
+    }
+}
+
+/// The chain as `assert` statements, in order
+fn instrument_chain(chain: &AssertionChain) -> Box<Stm> {
+    chain.0.iter()
+        .filter(|assertion| is_executable(assertion))
+        .map(|assertion| Box::new(Stm::Assert(Box::new(assertion.clone()))))
+        .fold(Box::new(Stm::Skip), seq)
+}
+
+/// The program of the block, where every assertion is checked by an `assert` at the point it annotates
+fn instrument_block(block: &AxBlock, total: bool) -> Result<Box<Stm>> {
+    let AxBlock(first, rem) = block;
+
+    let mut res = instrument_chain(first);
+    for (stm, chain) in rem {
+        res = seq(seq(res, instrument_stm(stm, total)?), instrument_chain(chain));
+    }
+    Ok(res)
+}
+
+/// The body of a loop, in a total correctness proof the logical variable of the variant is bound to the value of
+/// the variant at the start of each iteration
+fn instrument_loop_body(stm: &AxStm, inner_block: &AxBlock, total: bool) -> Result<Box<Stm>> {
+    let body = instrument_block(inner_block, total)?;
+    if !total {
+        return Ok(body);
+    }
+
+    let (_, variant, logical_var) = stm.try_get_while_things()?;
+    Ok(match logical_var {
+        Aexp::Var(v) => seq(Box::new(Stm::Assign(v.clone(), Box::new(variant.clone()))), body),
+        _ => body,
+    })
+}
+
+fn instrument_stm(stm: &AxStm, total: bool) -> Result<Box<Stm>> {
+    Ok(match stm {
+        AxStm::If(cond, then_block, else_block) => Box::new(Stm::If(
+            Box::new(cond.clone()), instrument_block(then_block, total)?, instrument_block(else_block, total)?
+        )),
+        AxStm::While(cond, inner_block) => Box::new(Stm::While(
+            Box::new(cond.clone()), instrument_loop_body(stm, inner_block, total)?
+        )),
+        AxStm::Repeat(inner_block, cond) => Box::new(Stm::Repeat(
+            instrument_loop_body(stm, inner_block, total)?, Box::new(cond.clone())
+        )),
+        AxStm::For(x, from, to, inner_block) => Box::new(Stm::For(
+            x.clone(), Box::new(from.clone()), Box::new(to.clone()), instrument_block(inner_block, total)?
+        )),
+        other => other.clone().into_stm(),
+    })
+}
+
+/// All integer variables occurring in the assertions and statements of the block
+pub(crate) fn block_vars(block: &AxBlock) -> HashSet<Var> {
+    let AxBlock(first, rem) = block;
+
+    let mut vars: HashSet<Var> = first.0.iter().flat_map(|assertion| assertion.free_vars()).collect();
+    for (stm, chain) in rem {
+        vars.extend(chain.0.iter().flat_map(|assertion| assertion.free_vars()));
+        match stm {
+            AxStm::Assign(x, aexp) => {
+                vars.insert(x.clone());
+                vars.extend(aexp.free_vars());
+            },
+            AxStm::ArrAssign(_, index, aexp) => {
+                vars.extend(index.free_vars());
+                vars.extend(aexp.free_vars());
+            },
+            AxStm::Assert(cond) | AxStm::Assume(cond) => vars.extend(cond.free_vars()),
+            AxStm::If(cond, then_block, else_block) => {
+                vars.extend(cond.free_vars());
+                vars.extend(block_vars(then_block));
+                vars.extend(block_vars(else_block));
+            },
+            AxStm::While(cond, inner_block) | AxStm::Repeat(inner_block, cond) => {
+                vars.extend(cond.free_vars());
+                vars.extend(block_vars(inner_block));
+            },
+            AxStm::For(x, from, to, inner_block) => {
+                vars.insert(x.clone());
+                vars.extend(from.free_vars());
+                vars.extend(to.free_vars());
+                vars.extend(block_vars(inner_block));
+            },
+            AxStm::Skip | AxStm::Abort => {},
+        }
+    }
+
+    vars
+}
+
+/// All arrays occurring in the assertions and statements of the block
+pub(crate) fn block_arrays(block: &AxBlock) -> HashSet<Var> {
+    let AxBlock(first, rem) = block;
+
+    let mut arrays: HashSet<Var> = first.0.iter().flat_map(|assertion| assertion.free_arrays()).collect();
+    for (stm, chain) in rem {
+        arrays.extend(chain.0.iter().flat_map(|assertion| assertion.free_arrays()));
+        match stm {
+            AxStm::Assign(_, aexp) => arrays.extend(aexp.free_arrays()),
+            AxStm::ArrAssign(a, index, aexp) => {
+                arrays.insert(a.clone());
+                arrays.extend(index.free_arrays());
+                arrays.extend(aexp.free_arrays());
+            },
+            AxStm::Assert(cond) | AxStm::Assume(cond) => arrays.extend(cond.free_arrays()),
+            AxStm::If(cond, then_block, else_block) => {
+                arrays.extend(cond.free_arrays());
+                arrays.extend(block_arrays(then_block));
+                arrays.extend(block_arrays(else_block));
+            },
+            AxStm::While(cond, inner_block) | AxStm::Repeat(inner_block, cond) => {
+                arrays.extend(cond.free_arrays());
+                arrays.extend(block_arrays(inner_block));
+            },
+            AxStm::For(_, from, to, inner_block) => {
+                arrays.extend(from.free_arrays());
+                arrays.extend(to.free_arrays());
+                arrays.extend(block_arrays(inner_block));
+            },
+            AxStm::Skip | AxStm::Abort => {},
+        }
+    }
+
+    arrays
+}
+
+/// Runs the program of the outline from `initial` and checks every assertion at the point it annotates.
+/// Returns whether the execution was conclusive, i.e. `false` if the precondition does not hold initially or the
+/// execution does not terminate normally within `MAX_STEPS` steps. A violated assertion is an error.
+pub fn check_outline(prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, initial: &State, total: bool) -> Result<bool> {
+    let pre = &prog.0.0[0];
+    if is_executable(pre) && !boolean_eval(&Box::new(pre.clone()), initial, funcdefs)? {
+        return Ok(false);
+    }
+
+    let sos = SOS::new(Configuration::Nonterminal(instrument_block(prog, total)?, initial.clone()), funcdefs.clone());
+    for config in sos.take(MAX_STEPS) {
+        match config? {
+            Configuration::Nonterminal(_, _) => {},
+            Configuration::Terminal(_) => return Ok(true),
+            Configuration::Failed(assertion, state) => return Err(AssertionViolation {
+                assertion,
+                initial: initial.entries(),
+                state: state.entries(),
+            }.into()),
+            // An `assume` or `abort` ends the execution, there is nothing to check afterwards
+            Configuration::Blocked(_, _) | Configuration::Stuck(_, _) => return Ok(false),
+        }
+    }
+
+    Ok(false)
+}
+
+/// Checks the outline for random initial states satisfying the precondition, see `check_outline`.
+/// Returns the number of conclusive executions. Since the initial states are arbitrary, executions ending in a
+/// runtime error like an integer overflow are inconclusive as well.
+pub fn check_outline_random(prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>, total: bool) -> Result<usize> {
+    let mut vars: Vec<Var> = block_vars(prog).into_iter().collect();
+    vars.sort();
+    let mut arrays: Vec<Var> = block_arrays(prog).into_iter().collect();
+    arrays.sort();
+
+    let mut sampler = Sampler::new();
+    let mut conclusive = 0;
+    for _ in 0..MAX_TRIES {
+        if conclusive == SAMPLES {
+            break;
+        }
+        match check_outline(prog, funcdefs, &sampler.next_state(&vars, &arrays, SAMPLE_BOUND), total) {
+            Ok(true) => conclusive += 1,
+            Ok(false) => {},
+            Err(errs) if errs.0.iter().all(|err| matches!(err, RuntimeError { .. })) => {},
+            Err(errs) => return Err(errs),
+        }
+    }
+
+    Ok(conclusive)
+}
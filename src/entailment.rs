@@ -1,11 +1,15 @@
 use egg::{*, rewrite as rw};
+use std::convert::TryFrom;
 
 define_language! {
     pub enum ImpExpr {
         "+" = Add([Id; 2]),
         "-" = Sub([Id; 2]),
         "*" = Mul([Id; 2]),
+        "/" = Div([Id; 2]),
+        "%" = Mod([Id; 2]),
         "^" = Pow([Id; 2]),
+        "~" = Neg(Id),
         "=" = Eq([Id; 2]),
         "#" = Ne([Id; 2]),
         "<=" = Le([Id; 2]),
@@ -60,7 +64,10 @@ impl Analysis<ImpExpr> for ConstantFolding {
             ImpExpr::Add([a, b]) => Some(Value::Number(x(a)?.num() + x(b)?.num())),
             ImpExpr::Sub([a, b]) => Some(Value::Number(x(a)?.num() - x(b)?.num())),
             ImpExpr::Mul([a, b]) => Some(Value::Number(x(a)?.num() * x(b)?.num())),
-            ImpExpr::Pow([a, b]) => Some(Value::Number(x(a)?.num().pow(x(b)?.num() as u32))),
+            ImpExpr::Div([a, b]) => Some(Value::Number(x(a)?.num().checked_div_euclid(x(b)?.num())?)),
+            ImpExpr::Mod([a, b]) => Some(Value::Number(x(a)?.num().checked_rem_euclid(x(b)?.num())?)),
+            ImpExpr::Neg(a) => Some(Value::Number(x(a)?.num().checked_neg()?)),
+            ImpExpr::Pow([a, b]) => Some(Value::Number(x(a)?.num().checked_pow(u32::try_from(x(b)?.num()).ok()?)?)),
 
             ImpExpr::Eq([a, b]) => Some(Value::Boolean(x(a)?.num() == x(b)?.num())),
             ImpExpr::Ne([a, b]) => Some(Value::Boolean(x(a)?.num() != x(b)?.num())),
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use lalrpop_util::ParseError;
 use crate::ast::{AxStm, Bexp};
@@ -50,21 +50,46 @@ pub enum ImpErrorInner {
         entailment_dst: Bexp,
         //TODO: change these two together into one enum perhaps
         is_unknown: bool,
-        untrue_model: Option<HashMap<String, i64>>
+        untrue_model: Option<HashMap<String, i64>>,
+        /// The variables of the program, the other variables of the model are logical variables
+        program_vars: HashSet<String>,
+        /// The conjuncts of the premise resp. conclusion with their values in the model, if Z3 determines them
+        src_conjuncts: Vec<(Bexp, Option<bool>)>,
+        dst_conjuncts: Vec<(Bexp, Option<bool>)>,
     },
     AxStructureError {
         actual: Bexp,
         expected: Bexp,
         stm: AxStm,
     },
+    RuntimeError {
+        msg: String,
+    },
+    /// A proof obligation `premise |= conclusion` of a lemma that could not be verified
+    LemmaError {
+        lemma: String,
+        premise: Bexp,
+        conclusion: Bexp,
+        is_unknown: bool,
+        untrue_model: Option<HashMap<String, i64>>
+    },
+    /// An assertion of a proof outline that does not hold when running the program from `initial`
+    AssertionViolation {
+        assertion: Bexp,
+        initial: HashMap<String, i64>,
+        state: HashMap<String, i64>,
+    },
     Other(String),
 }
 
 pub fn string_of_model(model: &HashMap<String, i64>) -> String {
+    let mut keys: Vec<&String> = model.keys().collect();
+    keys.sort();
+
     let mut res = String::new();
     res.push_str("{ ");
     let mut first = true;
-    for key in model.keys() {
+    for key in keys {
         if first {
             res.push_str(&format!("{}={}", key, model[key]));
             first = false;
@@ -76,26 +101,100 @@ pub fn string_of_model(model: &HashMap<String, i64>) -> String {
     res
 }
 
+/// The model with the program variables and the logical variables listed separately
+fn string_of_split_model(model: &HashMap<String, i64>, program_vars: &HashSet<String>) -> String {
+    let (program, logical): (HashMap<String, i64>, HashMap<String, i64>) = model.iter()
+        .map(|(var, val)| (var.clone(), *val))
+        .partition(|(var, _)| program_vars.contains(var));
+
+    match (program.is_empty(), logical.is_empty()) {
+        (false, false) => format!("Program variables: {}\nLogical variables: {}", string_of_model(&program), string_of_model(&logical)),
+        (true, false) => format!("Logical variables: {}", string_of_model(&logical)),
+        _ => format!("Program variables: {}", string_of_model(&program)),
+    }
+}
+
+fn string_of_conjuncts(conjuncts: &[(Bexp, Option<bool>)]) -> String {
+    conjuncts.iter()
+        .map(|(conjunct, value)| {
+            let value = match value {
+                Some(true) => "true ",
+                Some(false) => "false",
+                None => "?    ",
+            };
+            format!("    {} | {}", value, conjunct.pretty_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 impl Display for ImpErrorInner {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            ImpErrorInner::EntailmentError { entailment_src, entailment_dst, is_unknown: true, untrue_model } => {
+            ImpErrorInner::EntailmentError { entailment_src, entailment_dst, is_unknown: true, .. } => {
                 f.write_str(&format!(
                     "Was unable to prove the following entailment:\n{}\n|=\n{}\nThis does not mean your proof is incorrect, but possibly that there is too much logic in this step.",
                     entailment_src.pretty_string(),
                     entailment_dst.pretty_string(),
                 ))
             },
-            ImpErrorInner::EntailmentError { entailment_src, entailment_dst, is_unknown: false, untrue_model: Some(model) } => {
+            ImpErrorInner::EntailmentError {
+                entailment_src, entailment_dst, is_unknown: false, untrue_model: Some(model), program_vars, src_conjuncts, dst_conjuncts
+            } => {
                 f.write_str(&format!(
                     "The following entailment is incorrect:\
                     \n{}\n|=\n{}\n\
-                    It does not hold in the following model:\n{}",
+                    It does not hold in the following model:\n{}\n\
+                    The conjuncts of the premise evaluate to:\n{}\n\
+                    The conjuncts of the conclusion evaluate to:\n{}",
                     entailment_src.pretty_string(),
                     entailment_dst.pretty_string(),
+                    string_of_split_model(model, program_vars),
+                    string_of_conjuncts(src_conjuncts),
+                    string_of_conjuncts(dst_conjuncts),
+                ))
+            },
+            ImpErrorInner::LemmaError { lemma, premise, conclusion, is_unknown: true, .. } => {
+                f.write_str(&format!(
+                    "Was unable to prove the following obligation of lemma {}:\n{}\n|=\n{}",
+                    lemma,
+                    premise.pretty_string(),
+                    conclusion.pretty_string(),
+                ))
+            },
+            ImpErrorInner::LemmaError { lemma, premise, conclusion, is_unknown: false, untrue_model: Some(model) } => {
+                f.write_str(&format!(
+                    "The following obligation of lemma {} is incorrect:\n{}\n|=\n{}\n\
+                    It does not hold in the following model:\n{}",
+                    lemma,
+                    premise.pretty_string(),
+                    conclusion.pretty_string(),
                     string_of_model(model),
                 ))
             },
+            ImpErrorInner::AssertionViolation { assertion, initial, state } => {
+                f.write_str(&format!(
+                    "The following assertion does not hold when running the program from {}:\n{}\n\
+                    The state at that point is {}",
+                    string_of_model(initial),
+                    assertion.pretty_string(),
+                    string_of_model(state),
+                ))
+            },
+            ImpErrorInner::AxStructureError { actual, expected, stm: stm @ (AxStm::Assign(..) | AxStm::ArrAssign(..)) } => {
+                f.write_str(&format!(
+                    "The precondition of the assignment {} must be\n{}\nbut is\n{}\n\
+                    To fix this, insert the consequence step\n⊨\n{{ {:?} }}\nright before the assignment, so that only \
+                    this entailment remains to be proven.",
+                    stm.indent_string("".to_owned()),
+                    expected.pretty_string(),
+                    actual.pretty_string(),
+                    expected,
+                ))
+            },
+            ImpErrorInner::RuntimeError { msg } => {
+                f.write_str(&format!("Runtime error: {}", msg))
+            },
             other => f.write_str(&format!("{:?}", other))
         }
     }
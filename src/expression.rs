@@ -1,37 +1,245 @@
 use super::ast::*;
 use crate::state::State;
+use crate::error::{string_of_model, ImpErrorInner};
+use crate::Result;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
-fn unsupported_op(op: &Opcode) -> ! {
-    panic!("Operation {:?} is not valid IMP! You may only use {:?} in pre-/post-conditions.", op, op)
+/// The maximum number of nested function calls, deeper recursion is reported as a runtime error
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// Quantifiers are evaluated by enumerating the values of their variable, ranges with more values are a runtime error
+const MAX_QUANTIFIER_RANGE: i64 = 10_000;
+
+fn runtime_error<T>(msg: String) -> Result<T> {
+    Err(ImpErrorInner::RuntimeError { msg }.into())
+}
+
+// Division and modulo follow the SMT-LIB (Euclidean) definition used by Z3, i.e. for b # 0:
+// a = b * (a / b) + a % b and 0 <= a % b < |b|. Division by zero and negative exponents are errors.
+fn op_eval(left: i64, op: &Opcode, right: i64) -> Result<i64> {
+    let res = match op {
+        Opcode::Add => left.checked_add(right),
+        Opcode::Sub => left.checked_sub(right),
+        Opcode::Mul => left.checked_mul(right),
+        Opcode::Div | Opcode::Mod if right == 0 => {
+            return runtime_error(format!("division by zero in {} {:?} {}", left, op, right));
+        },
+        Opcode::Div => left.checked_div_euclid(right),
+        Opcode::Mod => left.checked_rem_euclid(right),
+        Opcode::Pow if right < 0 => {
+            return runtime_error(format!("negative exponent in {} {:?} {}", left, op, right));
+        },
+        Opcode::Pow => u32::try_from(right).ok().and_then(|exp| left.checked_pow(exp)),
+    };
+
+    match res {
+        Some(val) => Ok(val),
+        None => runtime_error(format!("integer overflow in {} {:?} {}", left, op, right)),
+    }
+}
+
+pub fn arithmetic_eval(aexp: &Box<Aexp>, state: &State, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<i64> {
+    aexp_eval(aexp, state, funcdefs, 0)
 }
 
-fn unsupported_exp(exp: &Aexp) -> ! {
-    panic!("{:?} is not valid IMP. You may only use this expression in pre-/post-conditions.", exp)
+pub fn boolean_eval(bexp: &Box<Bexp>, state: &State, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<bool> {
+    bexp_eval(bexp, state, funcdefs, 0)
+}
+
+fn aexp_eval(aexp: &Aexp, state: &State, funcdefs: &HashMap<String, ImpFuncDef>, depth: usize) -> Result<i64> {
+    match aexp {
+        Aexp::Numeral(num) => Ok(*num),
+        Aexp::Var(var) => Ok(state.get(var)),
+        Aexp::Op(left, op, right) => op_eval(aexp_eval(left, state, funcdefs, depth)?, op, aexp_eval(right, state, funcdefs, depth)?),
+        Aexp::Neg(inner) => {
+            let val = aexp_eval(inner, state, funcdefs, depth)?;
+            match val.checked_neg() {
+                Some(neg) => Ok(neg),
+                None => runtime_error(format!("integer overflow in -{}", val)),
+            }
+        },
+        Aexp::Select(arr, index) => select_eval(arr, aexp_eval(index, state, funcdefs, depth)?, state, funcdefs, depth),
+        Aexp::Ite(cond, then_aexp, else_aexp) => {
+            if bexp_eval(cond, state, funcdefs, depth)? {
+                aexp_eval(then_aexp, state, funcdefs, depth)
+            } else {
+                aexp_eval(else_aexp, state, funcdefs, depth)
+            }
+        },
+        Aexp::FuncApp(fname, args) => {
+            let (funcdef, call_state) = call_eval(fname, args, state, funcdefs, depth)?;
+            match &funcdef.body {
+                // The first clause whose guard holds applies, it is an error if there is none
+                ImpFuncBody::Clauses(clauses) => {
+                    for clause in clauses {
+                        let applies = match &clause.guard {
+                            Some(guard) => bexp_eval(guard, &call_state, funcdefs, depth + 1)?,
+                            None => true,
+                        };
+                        if applies {
+                            return aexp_eval(&clause.body, &call_state, funcdefs, depth + 1);
+                        }
+                    }
+                    runtime_error(format!("no clause of {} applies to the arguments {}", fname, string_of_model(call_state.vars())))
+                },
+                ImpFuncBody::Int(body) => aexp_eval(body, &call_state, funcdefs, depth + 1),
+                ImpFuncBody::Bool(_) => runtime_error(format!("the predicate {} is called as an integer function", fname)),
+            }
+        },
+    }
+}
+
+/// The definition of the called function and the state binding its parameters to the values of the arguments.
+/// Functions only see their parameters, all other variables are `0` in the body.
+fn call_eval<'a>(fname: &str, args: &[Aexp], state: &State, funcdefs: &'a HashMap<String, ImpFuncDef>, depth: usize) -> Result<(&'a ImpFuncDef, State)> {
+    let funcdef = match funcdefs.get(fname) {
+        Some(funcdef) => funcdef,
+        None => return runtime_error(format!("call of the undefined function {}", fname)),
+    };
+    if funcdef.args.len() != args.len() {
+        return runtime_error(format!(
+            "{} takes {} arguments, but is called with {}", fname, funcdef.args.len(), args.len()
+        ));
+    }
+    if depth >= MAX_CALL_DEPTH {
+        return runtime_error(format!("maximum call depth of {} exceeded in a call of {}", MAX_CALL_DEPTH, fname));
+    }
+
+    let mut call_state = State::new();
+    for (param, arg) in funcdef.args.iter().zip(args) {
+        call_state.update(param, aexp_eval(arg, state, funcdefs, depth)?);
+    }
+
+    Ok((funcdef, call_state))
 }
 
-pub fn arithmetic_eval(aexp: &Box<Aexp>, state: &State) -> i64 {
-    match aexp.as_ref() {
-        Aexp::Numeral(num) => *num,
-        Aexp::Var(var) => state.get(var),
-        Aexp::Op(left, Opcode::Add, right) => arithmetic_eval(left, state) + arithmetic_eval(right, state),
-        Aexp::Op(left, Opcode::Sub, right) => arithmetic_eval(left, state) - arithmetic_eval(right, state),
-        Aexp::Op(left, Opcode::Mul, right) => arithmetic_eval(left, state) * arithmetic_eval(right, state),
-        Aexp::Op(_, op, _) => unsupported_op(op),
-        exp => unsupported_exp(exp),
+fn select_eval(arr: &Array, index: i64, state: &State, funcdefs: &HashMap<String, ImpFuncDef>, depth: usize) -> Result<i64> {
+    match arr {
+        Array::Var(arr) => Ok(state.get_array(arr, index)),
+        Array::Store(arr, store_index, value) => {
+            if aexp_eval(store_index, state, funcdefs, depth)? == index {
+                aexp_eval(value, state, funcdefs, depth)
+            } else {
+                select_eval(arr, index, state, funcdefs, depth)
+            }
+        },
     }
 }
 
-pub fn boolean_eval(bexp: &Box<Bexp>, state: &State) -> bool {
-    match bexp.as_ref() {
-        Bexp::Not(bexp_inner) => !boolean_eval(bexp_inner, state),
-        Bexp::Bop(left, Bopcode::And, right) => boolean_eval(left, state) && boolean_eval(right, state),
-        Bexp::Bop(left, Bopcode::Or, right) => boolean_eval(left, state) || boolean_eval(right, state),
-        Bexp::Rop(left, Ropcode::Eq, right) => arithmetic_eval(left, state) == arithmetic_eval(right, state),
-        Bexp::Rop(left, Ropcode::Ne, right) => arithmetic_eval(left, state) != arithmetic_eval(right, state),
-        Bexp::Rop(left, Ropcode::Lt, right) => arithmetic_eval(left, state) < arithmetic_eval(right, state),
-        Bexp::Rop(left, Ropcode::Le, right) => arithmetic_eval(left, state) <= arithmetic_eval(right, state),
-        Bexp::Rop(left, Ropcode::Gt, right) => arithmetic_eval(left, state) > arithmetic_eval(right, state),
-        Bexp::Rop(left, Ropcode::Ge, right) => arithmetic_eval(left, state) >= arithmetic_eval(right, state),
+fn bexp_eval(bexp: &Bexp, state: &State, funcdefs: &HashMap<String, ImpFuncDef>, depth: usize) -> Result<bool> {
+    let aexp_eval = |aexp: &Aexp| aexp_eval(aexp, state, funcdefs, depth);
+
+    Ok(match bexp {
+        Bexp::Not(bexp_inner) => !bexp_eval(bexp_inner, state, funcdefs, depth)?,
+        Bexp::Bop(left, Bopcode::And, right) => bexp_eval(left, state, funcdefs, depth)? && bexp_eval(right, state, funcdefs, depth)?,
+        Bexp::Bop(left, Bopcode::Or, right) => bexp_eval(left, state, funcdefs, depth)? || bexp_eval(right, state, funcdefs, depth)?,
+        Bexp::Rop(left, Ropcode::Eq, right) => aexp_eval(left)? == aexp_eval(right)?,
+        Bexp::Rop(left, Ropcode::Ne, right) => aexp_eval(left)? != aexp_eval(right)?,
+        Bexp::Rop(left, Ropcode::Lt, right) => aexp_eval(left)? < aexp_eval(right)?,
+        Bexp::Rop(left, Ropcode::Le, right) => aexp_eval(left)? <= aexp_eval(right)?,
+        Bexp::Rop(left, Ropcode::Gt, right) => aexp_eval(left)? > aexp_eval(right)?,
+        Bexp::Rop(left, Ropcode::Ge, right) => aexp_eval(left)? >= aexp_eval(right)?,
+        Bexp::PredApp(pname, args) => {
+            let (funcdef, call_state) = call_eval(pname, args, state, funcdefs, depth)?;
+            match &funcdef.body {
+                ImpFuncBody::Bool(body) => bexp_eval(body, &call_state, funcdefs, depth + 1)?,
+                _ => return runtime_error(format!("the integer function {} is called as a predicate", pname)),
+            }
+        },
+        Bexp::Quant(quantifier, x, body) => {
+            let (lower, upper) = match quantifier_bounds(quantifier, x, body) {
+                Some(bounds) => bounds,
+                None => return runtime_error(format!(
+                    "{:?} cannot be evaluated, only quantifiers with bounds like `forall x. lo <= x and x < hi -> P` can", bexp
+                )),
+            };
+            let lo = lower.iter().map(aexp_eval).collect::<Result<Vec<i64>>>()?.into_iter().max().unwrap();
+            let hi = upper.iter().map(aexp_eval).collect::<Result<Vec<i64>>>()?.into_iter().min().unwrap();
+            if !matches!(hi.checked_sub(lo), Some(len) if len < MAX_QUANTIFIER_RANGE) {
+                return runtime_error(format!("the range {}..={} of {:?} is too large to be evaluated", lo, hi, bexp));
+            }
+
+            let mut bound_state = state.clone();
+            let mut res = *quantifier == Quantifier::Forall;
+            for val in lo..=hi {
+                bound_state.update(x, val);
+                if bexp_eval(body, &bound_state, funcdefs, depth)? != res {
+                    res = !res;
+                    break;
+                }
+            }
+            res
+        },
+    })
+}
+
+/// An inclusive bound of the variable of a quantifier
+enum Bound {
+    Lower(Aexp),
+    Upper(Aexp),
+}
+
+/// The bound of `x` given by a comparison `c`
+fn bound(x: &Var, c: &Bexp) -> Option<Bound> {
+    let var = Aexp::Var(x.clone());
+    let one = || Box::new(Aexp::Numeral(1));
+    let (left, op, right) = match c {
+        Bexp::Rop(left, op, right) => (left, op, right),
+        _ => return None,
+    };
+
+    // `x op e` is turned into `e op' x`
+    let (e, op) = if **right == var {
+        (left, op.clone())
+    } else if **left == var {
+        (right, match op {
+            Ropcode::Lt => Ropcode::Gt,
+            Ropcode::Le => Ropcode::Ge,
+            Ropcode::Gt => Ropcode::Lt,
+            Ropcode::Ge => Ropcode::Le,
+            other => other.clone(),
+        })
+    } else {
+        return None;
+    };
+    if e.free_vars().contains(x) {
+        return None;
+    }
+
+    match op {
+        Ropcode::Le => Some(Bound::Lower((**e).clone())),
+        Ropcode::Lt => Some(Bound::Lower(Aexp::Op(e.clone(), Opcode::Add, one()))),
+        Ropcode::Ge => Some(Bound::Upper((**e).clone())),
+        Ropcode::Gt => Some(Bound::Upper(Aexp::Op(e.clone(), Opcode::Sub, one()))),
+        _ => None,
     }
 }
 
+/// The lower and upper bounds of the variable of a bounded quantifier `forall x. lo <= x and x < hi -> P` or
+/// `exists x. lo <= x and x < hi and P`, which may be any comparisons of `x` with expressions not containing `x`.
+/// For values of `x` outside of them, the body of `forall` holds and the body of `exists` does not.
+pub(crate) fn quantifier_bounds(quantifier: &Quantifier, x: &Var, body: &Bexp) -> Option<(Vec<Aexp>, Vec<Aexp>)> {
+    let range = match (quantifier, body) {
+        (Quantifier::Forall, Bexp::Bop(premise, Bopcode::Or, _)) => match premise.as_ref() {
+            Bexp::Not(range) => range.conjuncts(),
+            _ => return None,
+        },
+        (Quantifier::Exists, body) => body.conjuncts(),
+        _ => return None,
+    };
+
+    let (mut lower, mut upper) = (vec![], vec![]);
+    for c in range {
+        match bound(x, c) {
+            Some(Bound::Lower(lo)) => lower.push(lo),
+            Some(Bound::Upper(hi)) => upper.push(hi),
+            None => {},
+        }
+    }
+
+    if lower.is_empty() || upper.is_empty() {
+        return None;
+    }
+    Some((lower, upper))
+}
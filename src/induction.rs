@@ -0,0 +1,188 @@
+use super::ast::*;
+use super::axiomatic::{build_funcmap, map_of_model, minimized_model};
+use super::error::ImpErrorInner::*;
+use crate::error::{err_acc, ImpErrors};
+use std::collections::HashMap;
+use z3::SatResult;
+
+use super::Result;
+
+fn tt() -> Bexp {
+    Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0)))
+}
+
+fn and(left: Bexp, right: Bexp) -> Bexp {
+    Bexp::Bop(Box::new(left), Bopcode::And, Box::new(right))
+}
+
+fn implies(left: Bexp, right: Bexp) -> Bexp {
+    Bexp::Bop(Box::new(Bexp::Not(Box::new(left))), Bopcode::Or, Box::new(right))
+}
+
+/// Splits a function body along its conditional expressions into the leaves `(guards, leaf)`
+fn branches(body: &Aexp, guards: Vec<Bexp>) -> Vec<(Vec<Bexp>, Aexp)> {
+    match body {
+        Aexp::Ite(cond, t, e) => {
+            let mut then_guards = guards.clone();
+            then_guards.push(*cond.clone());
+            let mut else_guards = guards;
+            else_guards.push(Bexp::Not(cond.clone()));
+
+            let mut res = branches(t, then_guards);
+            res.extend(branches(e, else_guards));
+            res
+        },
+        leaf => vec![(guards, leaf.clone())],
+    }
+}
+
+/// The proof obligations `(premise, conclusion)` of `lemma name by induction on n: P`.
+///
+/// The induction follows the first function `f` applied to `n` in `P`: there is one obligation per branch of the
+/// body of `f`, where every recursive call of `f` in the branch adds the induction hypothesis
+/// `0 <= m and m < n -> P[args ↦ call args]` with `m` the call's argument in the position of `n`.
+/// This is sound, since it is strong induction on the naturals for positive `n`, and for `n <= 0` there are no
+/// hypotheses.
+fn induction_obligations(lemma: &ImpLemma, n: &Var, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<Vec<(Bexp, Bexp)>> {
+    let p = &lemma.body;
+
+    let (fname, args, pos) = p.func_apps().into_iter().filter_map(|(fname, args)| {
+        let pos = args.iter().position(|arg| *arg == Aexp::Var(n.clone()))?;
+        Some((fname, args, pos))
+    }).next().ok_or(Other(format!(
+        "Lemma {}: induction on {} requires a function applied to {}", lemma.name, n, n
+    )))?;
+
+    let funcdef = funcdefs.get(&fname).ok_or(Other(format!("Function {} not found", fname)))?;
+    let body = funcdef.int_body().ok_or(Other(format!(
+        "Lemma {}: induction is only supported over integer-valued functions, but {} is a predicate",
+        lemma.name, fname
+    )))?;
+
+    // Instantiates the parameters of f with the arguments of the application in P
+    let params: Vec<(Var, Aexp)> = funcdef.args.iter().cloned().zip(args.iter().cloned()).collect();
+
+    Ok(branches(&body, vec![]).into_iter().map(|(guards, leaf)| {
+        let mut premises: Vec<Bexp> = guards.into_iter().map(|guard| guard.substitute_all(&params)).collect();
+
+        for (_, call_args) in leaf.func_apps().into_iter().filter(|(callee, _)| *callee == fname) {
+            let call_args: Vec<Aexp> = call_args.into_iter().map(|arg| arg.substitute_all(&params)).collect();
+            let measure = call_args[pos].clone();
+
+            // The variables passed to f in P are instantiated with the arguments of the recursive call
+            let hyp_substitution: Vec<(Var, Aexp)> = args.iter().zip(call_args).filter_map(|(arg, call_arg)| {
+                match arg {
+                    Aexp::Var(v) => Some((v.clone(), call_arg)),
+                    _ => None,
+                }
+            }).collect();
+            let hypothesis = p.clone().substitute_all(&hyp_substitution);
+
+            let decreasing = and(
+                Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Le, Box::new(measure.clone())),
+                Bexp::Rop(Box::new(measure), Ropcode::Lt, Box::new(Aexp::Var(n.clone()))),
+            );
+            premises.push(implies(decreasing, hypothesis));
+        }
+
+        let premise = premises.into_iter().reduce(and).unwrap_or_else(tt);
+        (premise, p.clone())
+    }).collect())
+}
+
+/// Checks all lemmas in the order of their declaration, assuming the axioms and the lemmas proven before.
+/// Axioms are not checked. Returns the axioms and the proven lemmas, which are the only facts that may be assumed
+/// by later proofs, along with the errors of the lemmas that could not be proven.
+pub fn verify_lemmas(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, lemmas: &[ImpLemma]) -> (Vec<ImpLemma>, Result<()>) {
+    let mut errs = ImpErrors(vec![]);
+    let mut proven: Vec<ImpLemma> = vec![];
+
+    for lemma in lemmas {
+        let obligations = match &lemma.kind {
+            LemmaKind::Axiom => {
+                proven.push(lemma.clone());
+                continue;
+            },
+            LemmaKind::Lemma => {
+                println!("Verifying lemma {}:", lemma.name);
+                vec![(tt(), lemma.body.clone())]
+            },
+            LemmaKind::Induction(n) => {
+                println!("Verifying lemma {} by induction on {}:", lemma.name, n);
+                match induction_obligations(lemma, n, funcdefs) {
+                    Ok(obligations) => obligations,
+                    Err(e) => {
+                        err_acc(&mut errs, Err(e));
+                        continue;
+                    }
+                }
+            },
+        };
+
+        let mut holds = true;
+        for (premise, conclusion) in obligations {
+            println!("{{ {:?} }} ⊨ {{ {:?} }}", premise, conclusion);
+            let res = check_obligation(cfg, funcdefs, &proven, lemma, &premise, &conclusion);
+            holds &= res.is_ok();
+            err_acc(&mut errs, res);
+        }
+        if holds {
+            proven.push(lemma.clone());
+        }
+    }
+
+    if !errs.0.is_empty() {
+        return (proven, Err(errs));
+    }
+
+    (proven, Ok(()))
+}
+
+fn check_obligation(
+    cfg: &z3::Config,
+    funcdefs: &HashMap<String, ImpFuncDef>,
+    assumed: &[ImpLemma],
+    lemma: &ImpLemma,
+    premise: &Bexp,
+    conclusion: &Bexp,
+) -> Result<()> {
+    let ctx = z3::Context::new(cfg);
+    let funcmap = build_funcmap(&ctx, funcdefs)?;
+
+    let solver = z3::Solver::new(&ctx);
+    for assumed_lemma in assumed {
+        solver.assert(&assumed_lemma.to_z3_axiom(&ctx, &funcmap));
+    }
+    solver.assert(&premise.to_z3_bool(&ctx, &funcmap));
+    solver.assert(&conclusion.to_z3_bool(&ctx, &funcmap).not());
+
+    match solver.check() {
+        SatResult::Unsat => {
+            println!("Verified.");
+            Ok(())
+        },
+        SatResult::Unknown => {
+            println!("ERROR! Couldn't prove or disprove. Unknown.");
+            Err(LemmaError {
+                lemma: lemma.name.clone(),
+                premise: premise.clone(),
+                conclusion: conclusion.clone(),
+                is_unknown: true,
+                untrue_model: None,
+            }.into())
+        },
+        SatResult::Sat => {
+            let mut fv = premise.free_vars();
+            fv.extend(conclusion.free_vars());
+
+            println!("ERROR! The obligation does not hold.");
+            Err(LemmaError {
+                lemma: lemma.name.clone(),
+                premise: premise.clone(),
+                conclusion: conclusion.clone(),
+                is_unknown: false,
+                untrue_model: Some(map_of_model(&ctx, minimized_model(&ctx, &solver, &fv), fv)),
+            }.into())
+        },
+    }
+}
@@ -0,0 +1,260 @@
+use super::ast::*;
+use super::bmc::find_counterexample;
+use super::error::ImpErrorInner::*;
+use super::symbolic::explore;
+use std::collections::{HashMap, HashSet};
+
+use super::Result;
+
+/// Invariants are tried with `k = 1, ..., MAX_K`
+const MAX_K: usize = 3;
+
+/// Candidates replace any subset of the occurrences of a term in the postcondition, if there are at most this many
+const MAX_OCCURRENCES: u32 = 4;
+
+fn tt() -> Bexp {
+    Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0)))
+}
+
+fn and(left: Bexp, right: Bexp) -> Bexp {
+    Bexp::Bop(Box::new(left), Bopcode::And, Box::new(right))
+}
+
+fn seqs(stms: Vec<Stm>) -> Stm {
+    stms.into_iter().fold(Stm::Skip, |res, stm| match res {
+        Stm::Skip => stm,
+        res => Stm::Seq(Box::new(res), Box::new(stm)),
+    })
+}
+
+fn flatten(stm: Stm, res: &mut Vec<Stm>) {
+    match stm {
+        Stm::Seq(stm1, stm2) => {
+            flatten(*stm1, res);
+            flatten(*stm2, res);
+        },
+        stm => res.push(stm),
+    }
+}
+
+fn has_loop(stm: &Stm) -> bool {
+    match stm {
+        Stm::While(_, _) | Stm::Repeat(_, _) | Stm::For(_, _, _, _) => true,
+        Stm::Seq(stm1, stm2) | Stm::If(_, stm1, stm2) => has_loop(stm1) || has_loop(stm2),
+        _ => false,
+    }
+}
+
+/// The variables and arrays assigned by a loop-free statement
+fn assigned(stm: &Stm, vars: &mut HashSet<Var>, arrays: &mut HashSet<Var>) {
+    match stm {
+        Stm::Assign(x, _) => {
+            vars.insert(x.clone());
+        },
+        Stm::ArrAssign(a, _, _) => {
+            arrays.insert(a.clone());
+        },
+        Stm::Seq(stm1, stm2) | Stm::If(_, stm1, stm2) => {
+            assigned(stm1, vars, arrays);
+            assigned(stm2, vars, arrays);
+        },
+        _ => {},
+    }
+}
+
+/// Assigns arbitrary values to `vars`, i.e. fresh variables `x@round` that occur nowhere else
+fn havoc(vars: &[Var], round: usize) -> Stm {
+    seqs(vars.iter().map(|x| Stm::Assign(x.clone(), Box::new(Aexp::Var(format!("{}@{}", x, round))))).collect())
+}
+
+/// Replaces the occurrences of `from` selected by the bits of `mask`, in the order they are visited.
+/// Bodies of quantifiers are left unchanged, since `to` could be captured.
+struct Replacer<'a> {
+    from: &'a Aexp,
+    to: &'a Aexp,
+    mask: u32,
+    occurrence: u32,
+}
+
+impl<'a> Replacer<'a> {
+    fn aexp(&mut self, aexp: &Aexp) -> Aexp {
+        if aexp == self.from {
+            self.occurrence += 1;
+            return if self.mask & (1 << (self.occurrence - 1)) != 0 { self.to.clone() } else { aexp.clone() };
+        }
+
+        match aexp {
+            Aexp::Numeral(_) | Aexp::Var(_) => aexp.clone(),
+            Aexp::Op(left, op, right) => Aexp::Op(Box::new(self.aexp(left)), op.clone(), Box::new(self.aexp(right))),
+            Aexp::Neg(inner) => Aexp::Neg(Box::new(self.aexp(inner))),
+            Aexp::Select(arr, index) => Aexp::Select(Box::new(self.array(arr)), Box::new(self.aexp(index))),
+            Aexp::FuncApp(fname, args) => Aexp::FuncApp(fname.clone(), args.iter().map(|arg| self.aexp(arg)).collect()),
+            Aexp::Ite(cond, then_aexp, else_aexp) => Aexp::Ite(
+                Box::new(self.bexp(cond)), Box::new(self.aexp(then_aexp)), Box::new(self.aexp(else_aexp))
+            ),
+        }
+    }
+
+    fn array(&mut self, arr: &Array) -> Array {
+        match arr {
+            Array::Var(_) => arr.clone(),
+            Array::Store(arr, index, value) => Array::Store(
+                Box::new(self.array(arr)), Box::new(self.aexp(index)), Box::new(self.aexp(value))
+            ),
+        }
+    }
+
+    fn bexp(&mut self, bexp: &Bexp) -> Bexp {
+        match bexp {
+            Bexp::Rop(left, rop, right) => Bexp::Rop(Box::new(self.aexp(left)), rop.clone(), Box::new(self.aexp(right))),
+            Bexp::Bop(left, bop, right) => Bexp::Bop(Box::new(self.bexp(left)), bop.clone(), Box::new(self.bexp(right))),
+            Bexp::Not(inner) => Bexp::Not(Box::new(self.bexp(inner))),
+            Bexp::Quant(_, _, _) => bexp.clone(),
+            Bexp::PredApp(pname, args) => Bexp::PredApp(pname.clone(), args.iter().map(|arg| self.aexp(arg)).collect()),
+        }
+    }
+}
+
+/// Candidate invariants of the loop `while guard do ... end` derived from the postcondition. Since the guard does not
+/// hold after the loop, e.g. `i = a` for the guard `i # a`, the postcondition `b = a * a` may be generalized to hold in
+/// every iteration by replacing some occurrences of `a` with `i`, like `b = a * i`.
+fn candidates(post: &Bexp, guard: &Bexp) -> Vec<Bexp> {
+    let one = || Box::new(Aexp::Numeral(1));
+    // (from, to, bound): after the loop `from = to` holds, and `bound` holds in every iteration
+    let replacements: Vec<(Aexp, Aexp, Option<Bexp>)> = match guard {
+        Bexp::Rop(x, Ropcode::Ne, y) => vec![((**y).clone(), (**x).clone(), None), ((**x).clone(), (**y).clone(), None)],
+        Bexp::Not(inner) => match inner.as_ref() {
+            Bexp::Rop(x, Ropcode::Eq, y) => vec![((**y).clone(), (**x).clone(), None), ((**x).clone(), (**y).clone(), None)],
+            _ => vec![],
+        },
+        Bexp::Rop(x, Ropcode::Lt, y) => vec![((**y).clone(), (**x).clone(), Some(Bexp::Rop(x.clone(), Ropcode::Le, y.clone())))],
+        Bexp::Rop(x, Ropcode::Gt, y) => vec![((**y).clone(), (**x).clone(), Some(Bexp::Rop(x.clone(), Ropcode::Ge, y.clone())))],
+        Bexp::Rop(x, Ropcode::Le, y) => vec![(
+            (**y).clone(),
+            Aexp::Op(x.clone(), Opcode::Sub, one()),
+            Some(Bexp::Rop(x.clone(), Ropcode::Le, Box::new(Aexp::Op(y.clone(), Opcode::Add, one())))),
+        )],
+        Bexp::Rop(x, Ropcode::Ge, y) => vec![(
+            (**y).clone(),
+            Aexp::Op(x.clone(), Opcode::Add, one()),
+            Some(Bexp::Rop(x.clone(), Ropcode::Ge, Box::new(Aexp::Op(y.clone(), Opcode::Sub, one())))),
+        )],
+        _ => vec![],
+    };
+
+    let mut res = vec![post.clone()];
+    for (from, to, bound) in replacements {
+        let mut counter = Replacer { from: &from, to: &to, mask: 0, occurrence: 0 };
+        counter.bexp(post);
+        if counter.occurrence > MAX_OCCURRENCES {
+            continue;
+        }
+
+        for mask in 1..(1 << counter.occurrence) {
+            let candidate = Replacer { from: &from, to: &to, mask, occurrence: 0 }.bexp(post);
+            res.push(match &bound {
+                Some(bound) => and(candidate, bound.clone()),
+                None => candidate,
+            });
+        }
+    }
+
+    res
+}
+
+/// Whether every execution of the loop-free `stm` from a state satisfying `pre` satisfies `post` and every `assert`
+fn holds(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, pre: &Bexp, stm: &Stm, post: &Bexp) -> bool {
+    match explore(cfg, stm, funcdefs, 0) {
+        Ok(paths) => matches!(find_counterexample(cfg, funcdefs, pre, &paths, post), Ok(None)),
+        Err(_) => false,
+    }
+}
+
+/// A top-level loop `while guard do body end`, reached by executing `prefix` from the initial state
+struct Loop<'a> {
+    prefix: Stm,
+    guard: &'a Bexp,
+    body: &'a Stm,
+    /// The variables assigned by the body
+    vars: Vec<Var>,
+}
+
+impl<'a> Loop<'a> {
+    /// Whether `inv` is a `k`-inductive invariant of the loop
+    fn is_k_inductive(&self, cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, pre: &Bexp, inv: &Bexp, k: usize) -> bool {
+        // Base case: the invariant holds at the start of the first k iterations
+        let mut base = Stm::Assert(Box::new(inv.clone()));
+        for _ in 1..k {
+            base = Stm::Seq(
+                Box::new(Stm::Assert(Box::new(inv.clone()))),
+                Box::new(Stm::If(
+                    Box::new(self.guard.clone()),
+                    Box::new(Stm::Seq(Box::new(self.body.clone()), Box::new(base))),
+                    Box::new(Stm::Skip),
+                )),
+            );
+        }
+        if !holds(cfg, funcdefs, pre, &Stm::Seq(Box::new(self.prefix.clone()), Box::new(base)), &tt()) {
+            return false;
+        }
+
+        // Inductive step: if the invariant holds at the start of k consecutive iterations, it holds at the next one
+        let mut step = vec![havoc(&self.vars, 0)];
+        for _ in 0..k {
+            step.push(Stm::Assume(Box::new(and(inv.clone(), self.guard.clone()))));
+            step.push(self.body.clone());
+        }
+        holds(cfg, funcdefs, &tt(), &seqs(step), inv)
+    }
+}
+
+/// Proves `{pre} stm {post}` for partial correctness, where the invariants of the loops are found by k-induction.
+/// Only `while` loops at the top level of the program without nested loops or array assignments are supported.
+pub fn prove(cfg: &z3::Config, pre: &Bexp, stm: &Stm, post: &Bexp, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+    let mut stms = vec![];
+    flatten(stm.clone(), &mut stms);
+
+    // Each loop is replaced by assigning arbitrary values that satisfy its invariant and the negated guard
+    let mut prefix: Vec<Stm> = vec![];
+    for (round, stm) in stms.into_iter().enumerate() {
+        match stm {
+            Stm::While(guard, body) if !has_loop(&body) => {
+                let (mut vars, mut arrays) = (HashSet::new(), HashSet::new());
+                assigned(&body, &mut vars, &mut arrays);
+                if !arrays.is_empty() {
+                    return Err(Other(format!("The loop while {:?} assigns to arrays, which is not supported", guard)).into());
+                }
+                let mut vars: Vec<Var> = vars.into_iter().collect();
+                vars.sort();
+
+                let lp = Loop { prefix: seqs(prefix.clone()), guard: &guard, body: &body, vars };
+                let found = (1..=MAX_K).find_map(|k| {
+                    candidates(post, &guard).into_iter()
+                        .find(|inv| lp.is_k_inductive(cfg, funcdefs, pre, inv, k))
+                        .map(|inv| (inv, k))
+                });
+
+                match found {
+                    Some((inv, k)) => {
+                        println!("Found the {}-inductive invariant {{ {:?} }} for the loop while {:?}.", k, inv, guard);
+                        prefix.push(havoc(&lp.vars, round + 1));
+                        prefix.push(Stm::Assume(Box::new(and(inv, Bexp::Not(guard)))));
+                    },
+                    None => return Err(Other(format!(
+                        "Could not find an invariant for the loop while {:?} by generalizing the postcondition", guard
+                    )).into()),
+                }
+            },
+            stm if has_loop(&stm) => {
+                return Err(Other("Only while loops at the top level of a program without nested loops are supported".to_owned()).into());
+            },
+            stm => prefix.push(stm),
+        }
+    }
+
+    if holds(cfg, funcdefs, pre, &seqs(prefix), post) {
+        Ok(())
+    } else {
+        Err(Other("The postcondition does not follow from the invariants found for the loops".to_owned()).into())
+    }
+}
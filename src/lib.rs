@@ -1,7 +1,7 @@
 #[macro_use] extern crate lalrpop_util;
 
 use std::collections::HashMap;
-use crate::ast::{Aexp, AxBlock, AxStm, Bexp, ImpFuncDef, Opcode, Ropcode, Stm};
+use crate::ast::{Aexp, AxBlock, Bexp, ImpFuncBody, ImpFuncDef, ImpLemma, Opcode, Ropcode, Stm};
 use error::ImpErrorInner;
 use error::ImpErrors;
 use error::ImpErrorInner::*;
@@ -11,31 +11,32 @@ lalrpop_mod!(pub imp_lang); // synthesized by LALRPOP
 pub mod ast;
 pub mod state;
 pub mod big_step;
+pub mod bmc;
 pub mod small_step;
 pub mod expression;
 pub mod axiomatic;
+pub mod concretize;
+pub mod contracts;
 pub mod entailment;
+pub mod symbolic;
+pub mod induction;
+pub mod sampling;
+pub mod kinduction;
+pub mod termination;
+pub mod testing;
 pub mod error;
 
 pub type Result<T> = core::result::Result<T, ImpErrors>;
 
-fn stm_prog_from_src(src: &str) -> Result<Box<Stm>> {
-    let prog_res = imp_lang::StmParser::new().parse(src);
-    let prog = if prog_res.is_err() {
-        imp_lang::AxBlockParser::new().parse(src)?.into_stm()
-    } else {
-        prog_res?
-    };
-    Ok(prog)
-}
-
-fn ax_from_src(src: &str) -> Result<(HashMap<String, ImpFuncDef>, AxBlock)> {
+/// The functions every program may use, i.e. `factorial`, which is written `n!`
+pub fn builtin_funcdefs() -> HashMap<String, ImpFuncDef> {
     let mut funcdefs = HashMap::new();
 
     funcdefs.insert("factorial".to_owned(), ImpFuncDef {
         name: "factorial".to_owned(),
         args: vec!["n".to_owned()],
-        body: Aexp::Ite(
+        decreases: Some(Aexp::Var("n".to_owned())),
+        body: ImpFuncBody::Int(Aexp::Ite(
             Box::new(Bexp::Rop(Box::new(Aexp::Var("n".to_owned())), Ropcode::Le, Box::new(Aexp::Numeral(0)))),
             Box::new(Aexp::Numeral(1)),
             Box::new(Aexp::Op(
@@ -43,16 +44,41 @@ fn ax_from_src(src: &str) -> Result<(HashMap<String, ImpFuncDef>, AxBlock)> {
                 Opcode::Mul,
                 Box::new(Aexp::FuncApp("factorial".to_owned(),
                                        vec![Aexp::Op(Box::new(Aexp::Var("n".to_owned())), Opcode::Sub, Box::new(Aexp::Numeral(1)))]))))
-        )
+        ))
     });
 
-    let (funcdefs_vec, prog): (Vec<ImpFuncDef>, AxBlock) = imp_lang::AxProgramParser::new().parse(src)?;
+    funcdefs
+}
+
+/// The program and the functions it may call, the assertions of a proof outline are ignored
+fn stm_prog_from_src(src: &str) -> Result<(HashMap<String, ImpFuncDef>, Box<Stm>)> {
+    match imp_lang::StmParser::new().parse(src) {
+        Ok(prog) => {
+            let funcdefs = builtin_funcdefs();
+            termination::verify_calls(&funcdefs, prog.calls())?;
+            Ok((funcdefs, prog))
+        },
+        Err(_) => {
+            let (funcdefs, _, prog) = ax_from_src(src)?;
+            Ok((funcdefs, prog.into_stm()))
+        },
+    }
+}
+
+fn ax_from_src(src: &str) -> Result<(HashMap<String, ImpFuncDef>, Vec<ImpLemma>, AxBlock)> {
+    let mut funcdefs = builtin_funcdefs();
+
+    let (funcdefs_vec, lemmas, prog): (Vec<ImpFuncDef>, Vec<ImpLemma>, AxBlock) = imp_lang::AxProgramParser::new().parse(src)?;
 
     for funcdef in funcdefs_vec {
         funcdefs.insert(funcdef.name.clone(), funcdef);
     }
 
-    Ok((funcdefs, prog))
+    let mut calls = prog.calls();
+    calls.extend(lemmas.iter().flat_map(|lemma| lemma.body.calls()));
+    termination::verify_calls(&funcdefs, calls)?;
+
+    Ok((funcdefs, lemmas, prog))
 }
 
 fn default_z3_cfg() -> z3::Config {
@@ -61,44 +87,337 @@ fn default_z3_cfg() -> z3::Config {
     cfg
 }
 
-pub fn run_big(src: &str) -> Result<State> {
-    let stm = stm_prog_from_src(src)?;
+pub fn run_big(src: &str) -> Result<Configuration> {
+    let (funcdefs, stm) = stm_prog_from_src(src)?;
 
-    let state = big_step::run(Configuration::Nonterminal(stm, State::new()));
+    let config = big_step::run(Configuration::Nonterminal(stm, State::new()), &funcdefs)?;
 
-    Ok(state)
+    Ok(config)
 }
 
 pub fn run_small(src: &str) -> Result<Configuration> {
-    let stm = stm_prog_from_src(src)?;
+    let (funcdefs, stm) = stm_prog_from_src(src)?;
 
-    let mut sos = small_step::SOS::new(Configuration::Nonterminal(stm.clone(), State::new()));
+    let mut sos = small_step::SOS::new(Configuration::Nonterminal(stm.clone(), State::new()), funcdefs);
 
-    Ok(sos.run_execution())
+    sos.run_execution()
 }
 
 
 
 pub fn run_ax_partial(src: &str) -> Result<()> {
-    let (fdefs, prog) = ax_from_src(src)?;
+    let (fdefs, lemmas, prog) = ax_from_src(src)?;
+
+    // Admit terminating functions only
+    termination::verify_funcdefs(&default_z3_cfg(), &fdefs)?;
+
+    // Prove lemmas, only the proven ones and the axioms are assumed below
+    let (lemmas, lemmas_res) = induction::verify_lemmas(&default_z3_cfg(), &fdefs, &lemmas);
+    lemmas_res?;
 
     // Analyze structure
     axiomatic::verify_block_except_cons_partial(&prog)?;
 
     // Analyze entailments
-    axiomatic::verify_cons_partial(&default_z3_cfg(), &prog, &fdefs)?;
+    axiomatic::verify_cons_partial(&default_z3_cfg(), &prog, &fdefs, &lemmas)?;
 
     Ok(())
 }
 
 pub fn run_ax_total(src: &str) -> Result<()> {
-    let (fdefs, prog) = ax_from_src(src)?;
+    let (fdefs, lemmas, prog) = ax_from_src(src)?;
+
+    // Admit terminating functions only
+    termination::verify_funcdefs(&default_z3_cfg(), &fdefs)?;
+
+    // Prove lemmas, only the proven ones and the axioms are assumed below
+    let (lemmas, lemmas_res) = induction::verify_lemmas(&default_z3_cfg(), &fdefs, &lemmas);
+    lemmas_res?;
 
     // Analyze structure
     axiomatic::verify_block_except_cons_total(&prog)?;
 
     // Analyze entailments
-    axiomatic::verify_cons_total(&default_z3_cfg(), &prog, &fdefs)?;
+    axiomatic::verify_cons_total(&default_z3_cfg(), &prog, &fdefs, &lemmas)?;
+
+    Ok(())
+}
+
+/// Runs the program of a proof outline and checks its assertions, either from `initial` or from random initial
+/// states satisfying the precondition
+pub fn run_check(src: &str, initial: Option<State>, total: bool) -> Result<()> {
+    let (fdefs, _, prog) = ax_from_src(src)?;
+
+    match initial {
+        Some(initial) => {
+            if contracts::check_outline(&prog, &fdefs, &initial, total)? {
+                println!("All assertions hold.");
+            } else {
+                println!("The execution is inconclusive: the precondition does not hold, or the program was blocked, aborted or did not terminate.");
+            }
+        },
+        None => {
+            let conclusive = contracts::check_outline_random(&prog, &fdefs, total)?;
+            println!("All assertions hold in {} executions from random initial states.", conclusive);
+        },
+    }
+
+    let unchecked = contracts::unchecked_assertions(&prog);
+    if unchecked > 0 {
+        println!("{} assertions contain unbounded quantifiers and were not checked.", unchecked);
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Tests the pre- and postcondition of a proof outline by running the program from many initial states
+pub fn run_test(src: &str) -> Result<()> {
+    let (fdefs, _, prog) = ax_from_src(src)?;
+
+    let passed = testing::test_spec(&default_z3_cfg(), &prog, &fdefs)?;
+    println!("The postcondition holds in {} executions.", passed);
+
+    Ok(())
+}
+
+/// Prints every feasible path of the program, with loops unrolled at most `max_iterations` times
+pub fn run_symbolic(src: &str, max_iterations: usize) -> Result<()> {
+    let (fdefs, stm) = stm_prog_from_src(src)?;
+
+    let paths = symbolic::explore(&default_z3_cfg(), &stm, &fdefs, max_iterations)?;
+    let unfinished = paths.iter().filter(|path| matches!(path.end, symbolic::PathEnd::Unfinished)).count();
+    println!("Found {} feasible paths, {} of which exceed {} loop iterations:", paths.len(), unfinished, max_iterations);
+    for path in &paths {
+        println!("{}", path.indent_string("".to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks the pre- and postcondition of a proof outline for all executions with at most `max_iterations` loop iterations
+pub fn run_bmc(src: &str, max_iterations: usize) -> Result<()> {
+    let (fdefs, _, prog) = ax_from_src(src)?;
+
+    if bmc::check_bounded(&default_z3_cfg(), &prog, &fdefs, max_iterations)? {
+        println!("Verified partial correctness: no execution runs a loop more than {} times.", max_iterations);
+    } else {
+        println!("Verified all executions that run every loop at most {} times.", max_iterations);
+    }
+
+    Ok(())
+}
+
+/// The functions a program may call, with its pre- and postcondition
+type Spec = (HashMap<String, ImpFuncDef>, Bexp, Box<Stm>, Bexp);
+
+/// A program with its pre- and postcondition, either without intermediate assertions or as a proof outline
+fn spec_from_src(src: &str) -> Result<Spec> {
+    if let Ok((funcdefs_vec, pre, stm, post)) = imp_lang::SpecParser::new().parse(src) {
+        let mut funcdefs = builtin_funcdefs();
+        funcdefs.extend(funcdefs_vec.into_iter().map(|funcdef| (funcdef.name.clone(), funcdef)));
+        return Ok((funcdefs, pre, stm, post));
+    }
+
+    let (funcdefs, _, prog) = ax_from_src(src)?;
+    let (pre, stm, post) = prog.spec();
+    Ok((funcdefs, pre, stm, post))
+}
+
+pub fn run_kinduction(src: &str) -> Result<()> {
+    let (fdefs, pre, stm, post) = spec_from_src(src)?;
+
+    kinduction::prove(&default_z3_cfg(), &pre, &stm, &post, &fdefs)?;
+    println!("Verified partial correctness.");
+
+    Ok(())
+}
+
+pub fn run_fmt(src: &str) -> Result<String> {
+    let body = match imp_lang::StmParser::new().parse(src) {
+        Ok(stm) => stm.indent_string("".to_owned()),
+        Err(_) => {
+            let (funcdefs, lemmas, prog) = imp_lang::AxProgramParser::new().parse(src)?;
+            ax_program_string(&funcdefs, &lemmas, &prog)
+        }
+    };
+
+    Ok(reattach_comments(src, &body))
+}
+
+/// Inserts a consequence step before every assignment whose precondition does not fit its postcondition, see
+/// `axiomatic::assignment_fixes`. The steps are inserted into the source text, so that its comments and layout are kept.
+/// Returns the number of inserted steps and the fixed source, if any were inserted.
+pub fn run_fix(src: &str) -> Result<Option<(usize, String)>> {
+    let (funcdefs, _, prog) = imp_lang::AxProgramParser::new().parse(src)?;
+
+    let fixes = axiomatic::assignment_fixes(&prog, &mut 0);
+    if fixes.is_empty() {
+        return Ok(None);
+    }
+
+    // The bodies of integer functions are the only other braces before the outline
+    let int_bodies = funcdefs.iter().filter(|funcdef| matches!(funcdef.body, ImpFuncBody::Int(_))).count();
+    let ends = assertion_ends(src, int_bodies);
+
+    let mut fixed = src.to_owned();
+    for (index, assertion) in fixes.iter().rev() {
+        let mut end = ends[*index];
+        // A comment after the assertion stays on its line
+        let line_end = src[end..].find('\n').map_or(src.len(), |idx| end + idx);
+        let rest = src[end..line_end].trim_start();
+        if rest.is_empty() || rest.starts_with("//") {
+            end = line_end;
+        }
+        let line_start = src[..end].rfind('\n').map_or(0, |idx| idx + 1);
+        let indent: String = src[line_start..end].chars().take_while(|c| c.is_whitespace()).collect();
+        fixed.insert_str(end, &format!("\n{}⊨\n{}{{ {:?} }}", indent, indent, assertion));
+    }
+
+    Ok(Some((fixes.len(), fixed)))
+}
+
+/// The offsets right after the closing braces of the assertions of an outline, in the order they are written.
+/// The braces in comments and of the first `skip` braced function bodies are left out.
+fn assertion_ends(src: &str, skip: usize) -> Vec<usize> {
+    let mut ends = vec![];
+    let mut i = 0;
+
+    while i < src.len() {
+        let rest = &src[i..];
+        if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            i += rest.find("*/").map_or(rest.len(), |idx| idx + 2);
+        } else {
+            if rest.starts_with('}') {
+                ends.push(i + 1);
+            }
+            i += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+
+    ends.into_iter().skip(skip).collect()
+}
+
+fn ax_program_string(funcdefs: &[ImpFuncDef], lemmas: &[ImpLemma], prog: &AxBlock) -> String {
+    let mut parts: Vec<String> = funcdefs.iter().map(|funcdef| funcdef.indent_string("".to_owned())).collect();
+    parts.extend(lemmas.iter().map(|lemma| lemma.indent_string("".to_owned())));
+    parts.push(prog.indent_string("".to_owned()));
+    parts.join("\n\n")
+}
+
+/// A comment of the source and the index of the source token following it
+struct Comment<'a> {
+    text: &'a str,
+    /// The whitespace between the code before a comment on the same line and the comment, `None` if there is no code
+    gap: Option<&'a str>,
+    next_token: usize,
+}
+
+/// The tokens of the source with their offsets, and its comments. Every character that is not part of a name or a
+/// numeral is a token of its own, which suffices to match the tokens of the source with those of the formatted source.
+fn tokenize(src: &str) -> (Vec<(usize, &str)>, Vec<Comment<'_>>) {
+    let mut tokens = vec![];
+    let mut comments = vec![];
+    let mut i = 0;
+
+    while i < src.len() {
+        let rest = &src[i..];
+        let c = rest.chars().next().unwrap();
+        if rest.starts_with("//") || rest.starts_with("/*") {
+            let len = if rest.starts_with("//") {
+                rest.find('\n').unwrap_or(rest.len())
+            } else {
+                rest.find("*/").map_or(rest.len(), |idx| idx + 2)
+            };
+            let before = &src[src[..i].rfind('\n').map_or(0, |idx| idx + 1)..i];
+            let gap = if before.trim().is_empty() { None } else { Some(&before[before.trim_end().len()..]) };
+            comments.push(Comment { text: &rest[..len], gap, next_token: tokens.len() });
+            i += len;
+        } else if c.is_whitespace() {
+            i += c.len_utf8();
+        } else {
+            let len = if c.is_alphanumeric() || c == '_' {
+                rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len())
+            } else {
+                c.len_utf8()
+            };
+            tokens.push((i, &rest[..len]));
+            i += len;
+        }
+    }
+
+    (tokens, comments)
+}
+
+/// For every token of `a`, the index of the token of `b` it is matched with in a longest common subsequence, if any
+fn align(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    // lengths[i][j] is the length of a longest common subsequence of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut matched = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matched[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    matched
+}
+
+/// Puts the comments of the source into the formatted source `body`, since the lexer skips them. A comment on a line
+/// of its own goes on a line of its own before the line of the code following it, and any other comment goes at the
+/// end of the line of the code preceding it.
+fn reattach_comments(src: &str, body: &str) -> String {
+    let (src_tokens, comments) = tokenize(src);
+    let (body_tokens, _) = tokenize(body);
+    let src_strs: Vec<&str> = src_tokens.iter().map(|(_, token)| *token).collect();
+    let body_strs: Vec<&str> = body_tokens.iter().map(|(_, token)| *token).collect();
+    let matched = align(&src_strs, &body_strs);
+    let line_of = |j: &usize| body[..body_tokens[*j].0].matches('\n').count();
+
+    let mut lines: Vec<String> = body.split('\n').map(|line| line.to_owned()).collect();
+    // The comments on lines of their own before every line, and after the last one
+    let mut before: Vec<Vec<&str>> = vec![vec![]; lines.len() + 1];
+    for comment in comments {
+        match comment.gap {
+            Some(gap) => match matched[..comment.next_token].iter().rev().flatten().next().map(line_of) {
+                Some(line) => lines[line] += &format!("{}{}", gap, comment.text),
+                None => before[0].push(comment.text),
+            },
+            None => {
+                let line = matched[comment.next_token..].iter().flatten().next().map_or(lines.len(), line_of);
+                before[line].push(comment.text);
+            },
+        }
+    }
+
+    let mut res = String::new();
+    for (line, comments) in before.iter().enumerate() {
+        let indent: String = lines.get(line).map_or("".to_owned(), |line| line.chars().take_while(|c| c.is_whitespace()).collect());
+        for comment in comments {
+            res += &format!("{}{}\n", indent, comment);
+        }
+        if let Some(line) = lines.get(line) {
+            res += &format!("{}\n", line);
+        }
+    }
+
+    res
+}
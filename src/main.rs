@@ -1,11 +1,9 @@
 use imp::*;
 
 use state::{Configuration, State};
-use ast::*;
 use std::env;
 use std::fs;
 use z3;
-use std::collections::HashMap;
 use ast::Bexp::Rop;
 
 
@@ -13,8 +11,110 @@ fn main() {
 
     let args: Vec<String> = env::args().collect();
 
+    if args.len() == 3 && args[1] == "fmt" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        match run_fmt(contents.as_str()) {
+            Ok(formatted) => print!("{}", formatted),
+            Err(errs) => errs.0.iter().for_each(|err| println!("ERROR: {}", err)),
+        }
+        return;
+    }
+
+    if (args.len() == 3 || args.len() == 4) && args[1] == "symbolic" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        let max_iterations = match args.get(3).map(|arg| arg.parse::<usize>()) {
+            None => 5,
+            Some(Ok(max_iterations)) => max_iterations,
+            Some(Err(_)) => {
+                println!("ERROR: Expected a maximum number of loop iterations, found {}", args[3]);
+                return;
+            },
+        };
+
+        if let Err(errs) = run_symbolic(contents.as_str(), max_iterations) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        return;
+    }
+
+    if (args.len() == 3 || args.len() == 4) && args[1] == "bmc" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        let max_iterations = match args.get(3).map(|arg| arg.parse::<usize>()) {
+            None => 5,
+            Some(Ok(max_iterations)) => max_iterations,
+            Some(Err(_)) => {
+                println!("ERROR: Expected a maximum number of loop iterations, found {}", args[3]);
+                return;
+            },
+        };
+
+        if let Err(errs) = run_bmc(contents.as_str(), max_iterations) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        return;
+    }
+
+    if args.len() == 3 && args[1] == "kinduction" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        if let Err(errs) = run_kinduction(contents.as_str()) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        return;
+    }
+
+    if args.len() == 3 && args[1] == "test" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        if let Err(errs) = run_test(contents.as_str()) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        return;
+    }
+
+    if args.len() >= 4 && args[1] == "check" {
+        let contents = fs::read_to_string(&args[2])
+            .expect("Something went wrong reading the file");
+
+        // The initial state is given as `x=1 y=2`, without any assignments the initial states are random
+        let initial = if args.len() > 4 {
+            let mut state = State::new();
+            for assignment in &args[4..] {
+                match assignment.split_once('=').map(|(var, val)| (var, val.parse::<i64>())) {
+                    Some((var, Ok(val))) => state.update(&var.to_owned(), val),
+                    _ => {
+                        println!("ERROR: Expected an assignment like x=1, found {}", assignment);
+                        return;
+                    },
+                }
+            }
+            Some(state)
+        } else {
+            None
+        };
+
+        if let Err(errs) = run_check(contents.as_str(), initial, args[3] == "total") {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        return;
+    }
+
     if args.len() < 5 {
-        println!("Usage: ./imp <filename> <true/false: run big-step> <true/false: run small-step> <total/partial/false: run axiomatic>");
+        println!("Usage: ./imp <filename> <true/false: run big-step> <true/false: run small-step> <total/partial/false: run axiomatic> [--concretize] [--fix]");
+        println!("       ./imp fmt <filename>");
+        println!("       ./imp test <filename>");
+        println!("       ./imp symbolic <filename> [max loop iterations]");
+        println!("       ./imp bmc <filename> [max loop iterations]");
+        println!("       ./imp kinduction <filename>");
+        println!("       ./imp check <filename> <total/partial> [x=1 y=2 ...]");
         println!("Example: ./imp examples/square.imp false false partial");
         return;
     }
@@ -24,57 +124,73 @@ fn main() {
     let run_big = &args[2];
     let run_small = &args[3];
     let run_axiomatic = &args[4];
+    // With `--concretize`, the counterexamples of failing entailments are run with the big-step semantics
+    let concretize = args[5..].iter().any(|arg| arg == "--concretize");
+    // With `--fix`, consequence steps are inserted before assignments whose precondition does not fit
+    let fix = args[5..].iter().any(|arg| arg == "--fix");
     println!("Reading file {}...", filename);
 
-    let contents = fs::read_to_string(filename)
+    let mut contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
+    if fix {
+        match run_fix(contents.as_str()) {
+            Ok(Some((count, fixed))) => {
+                let backup = format!("{}.bak", filename);
+                fs::write(&backup, &contents).expect("Something went wrong writing the backup");
+                fs::write(filename, &fixed).expect("Something went wrong writing the file");
+                println!("Inserted {} consequence steps into {}, only their entailments remain to be proven.", count, filename);
+                println!("The original file is kept as {}.", backup);
+                contents = fixed;
+            },
+            Ok(None) => println!("All assignments fit their postconditions, there is nothing to fix."),
+            Err(errs) => errs.0.iter().for_each(|err| println!("ERROR: {}", err)),
+        }
+    }
+
     if run_big == "true" {
         // Allow both pure IMP syntax and pre/post-condition syntax
         let prog_res = imp_lang::StmParser::new().parse(contents.as_str());
-        let prog = if prog_res.is_err() {
-            imp_lang::AxBlockParser::new().parse(contents.as_str()).unwrap().into_stm()
+        let (funcdefs, prog) = if prog_res.is_err() {
+            let (funcdefs_vec, _, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
+            let mut funcdefs = builtin_funcdefs();
+            funcdefs.extend(funcdefs_vec.into_iter().map(|funcdef| (funcdef.name.clone(), funcdef)));
+            (funcdefs, prog.into_stm())
         } else {
-            prog_res.unwrap()
+            (builtin_funcdefs(), prog_res.unwrap())
         };
         // let prog = imp_lang::StmParser::new().parse(contents.as_str()).unwrap_or(imp_lang::AxBlockParser::new().parse(contents.as_str()).unwrap().into_stm());
         println!("\nRunning big-step evaluator...");
-        println!("Big-step result: {:?}", big_step::run(Configuration::Nonterminal(prog, State::new())));
+        match big_step::run(Configuration::Nonterminal(prog, State::new()), &funcdefs) {
+            Ok(config) => println!("Big-step result: {:?}", config),
+            Err(errs) => errs.0.iter().for_each(|err| println!("ERROR: {}", err)),
+        }
     }
     if run_small == "true" {
         // Allow both pure IMP syntax and pre/post-condition syntax
         let prog_res = imp_lang::StmParser::new().parse(contents.as_str());
-        let prog = if prog_res.is_err() {
-            imp_lang::AxBlockParser::new().parse(contents.as_str()).unwrap().into_stm()
+        let (funcdefs, prog) = if prog_res.is_err() {
+            let (funcdefs_vec, _, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
+            let mut funcdefs = builtin_funcdefs();
+            funcdefs.extend(funcdefs_vec.into_iter().map(|funcdef| (funcdef.name.clone(), funcdef)));
+            (funcdefs, prog.into_stm())
         } else {
-            prog_res.unwrap()
+            (builtin_funcdefs(), prog_res.unwrap())
         };
         // let prog = imp_lang::StmParser::new().parse(contents.as_str()).unwrap_or(imp_lang::AxBlockParser::new().parse(contents.as_str()).unwrap().into_stm());
         println!("\nRunning small-step evaluator...");
-        let mut sos = small_step::SOS::new(Configuration::Nonterminal(prog.clone(), State::new()));
-        sos.run_execution();
+        let mut sos = small_step::SOS::new(Configuration::Nonterminal(prog.clone(), State::new()), funcdefs);
+        if let Err(errs) = sos.run_execution() {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
     }
 
     // Setup built-in IMP functions
-    let mut funcdefs = HashMap::new();
-
-    funcdefs.insert("factorial".to_owned(), ImpFuncDef {
-        name: "factorial".to_owned(),
-        args: vec!["n".to_owned()],
-        body: Aexp::Ite(
-            Box::new(Bexp::Rop(Box::new(Aexp::Var("n".to_owned())), Ropcode::Le, Box::new(Aexp::Numeral(0)))),
-            Box::new(Aexp::Numeral(1)),
-            Box::new(Aexp::Op(
-                Box::new(Aexp::Var("n".to_owned())),
-                Opcode::Mul,
-                Box::new(Aexp::FuncApp("factorial".to_owned(),
-                                       vec![Aexp::Op(Box::new(Aexp::Var("n".to_owned())), Opcode::Sub, Box::new(Aexp::Numeral(1)))]))))
-        )
-    });
+    let mut funcdefs = builtin_funcdefs();
 
     if run_axiomatic == "partial" || run_axiomatic == "true" {
         // Force syntax with pre/post-conditions
-        let (funcdefs_vec, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
+        let (funcdefs_vec, lemmas, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
         println!("\nVerifying partial correctness for program using axiomatic semantics...");
         println!("{:?}\n", prog);
 
@@ -82,16 +198,47 @@ fn main() {
             funcdefs.insert(funcdef.name.clone(), funcdef);
         }
 
+        let mut calls = prog.calls();
+        calls.extend(lemmas.iter().flat_map(|lemma| lemma.body.calls()));
+        if let Err(errs) = termination::verify_calls(&funcdefs, calls) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            println!("Not verifying the program, since it calls functions that are not defined as called.");
+            return;
+        }
+
         let mut cfg = z3::Config::new();
         cfg.set_timeout_msec(5000);
-        axiomatic::verify_block_except_cons_partial(&prog);
-        axiomatic::verify_cons_partial(&cfg, &prog, &funcdefs);
+        // Functions that may not terminate could make the background theory inconsistent
+        if let Err(errs) = termination::verify_funcdefs(&cfg, &funcdefs) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            println!("Not verifying the entailments, since not all functions could be admitted.");
+            return;
+        }
+        // Only the axioms and the lemmas that could be proven are assumed in the entailments
+        let (lemmas, lemmas_res) = induction::verify_lemmas(&cfg, &funcdefs, &lemmas);
+        if let Err(errs) = lemmas_res {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        if let Err(errs) = axiomatic::verify_block_except_cons_partial(&prog) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            if !fix && errs.0.iter().any(is_assignment_structure_error) {
+                println!("Running with --fix inserts the proposed consequence steps into {}.", filename);
+            }
+        }
+        if let Err(errs) = axiomatic::verify_cons_partial(&cfg, &prog, &funcdefs, &lemmas) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        if concretize {
+            if let Err(errs) = concretize::concretize_entailments(&cfg, &prog, &funcdefs, &lemmas) {
+                errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            }
+        }
         println!("Successfully verified partial correctness of program. (if there are no ERRORs)");
     }
 
     if run_axiomatic == "total" {
         // Force syntax with pre/post-conditions
-        let (funcdefs_vec, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
+        let (funcdefs_vec, lemmas, prog) = imp_lang::AxProgramParser::new().parse(contents.as_str()).unwrap();
         println!("\nVerifying total correctness for program using axiomatic semantics...");
         println!("{:?}\n", prog);
 
@@ -99,12 +246,50 @@ fn main() {
             funcdefs.insert(funcdef.name.clone(), funcdef);
         }
 
+        let mut calls = prog.calls();
+        calls.extend(lemmas.iter().flat_map(|lemma| lemma.body.calls()));
+        if let Err(errs) = termination::verify_calls(&funcdefs, calls) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            println!("Not verifying the program, since it calls functions that are not defined as called.");
+            return;
+        }
+
         let mut cfg = z3::Config::new();
         cfg.set_timeout_msec(5000);
-        axiomatic::verify_block_except_cons_total(&prog);
-        axiomatic::verify_cons_total(&cfg, &prog, &funcdefs);
+        // Functions that may not terminate could make the background theory inconsistent
+        if let Err(errs) = termination::verify_funcdefs(&cfg, &funcdefs) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            println!("Not verifying the entailments, since not all functions could be admitted.");
+            return;
+        }
+        // Only the axioms and the lemmas that could be proven are assumed in the entailments
+        let (lemmas, lemmas_res) = induction::verify_lemmas(&cfg, &funcdefs, &lemmas);
+        if let Err(errs) = lemmas_res {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        if let Err(errs) = axiomatic::verify_block_except_cons_total(&prog) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            if !fix && errs.0.iter().any(is_assignment_structure_error) {
+                println!("Running with --fix inserts the proposed consequence steps into {}.", filename);
+            }
+        }
+        if let Err(errs) = axiomatic::verify_cons_total(&cfg, &prog, &funcdefs, &lemmas) {
+            errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+        }
+        if concretize {
+            if let Err(errs) = concretize::concretize_entailments(&cfg, &prog, &funcdefs, &lemmas) {
+                errs.0.iter().for_each(|err| println!("ERROR: {}", err));
+            }
+        }
         println!("Successfully verified total correctness of program. (if there are no ERRORs)");
     }
 
     entailment::example()
 }
+
+fn is_assignment_structure_error(err: &error::ImpErrorInner) -> bool {
+    matches!(
+        err,
+        error::ImpErrorInner::AxStructureError { stm: ast::AxStm::Assign(..) | ast::AxStm::ArrAssign(..), .. }
+    )
+}
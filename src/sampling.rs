@@ -0,0 +1,58 @@
+use super::ast::Var;
+use super::state::State;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The maximum number of small steps of one execution, longer executions are considered non-terminating
+pub(crate) const MAX_STEPS: usize = 10_000;
+
+/// The number of random initial states that are executed
+pub(crate) const SAMPLES: usize = 100;
+
+/// Initial states that do not satisfy the precondition are discarded, up to this many tries
+pub(crate) const MAX_TRIES: usize = 1_000;
+
+/// Variables and array entries of random initial states take values in `-SAMPLE_BOUND..=SAMPLE_BOUND`
+pub(crate) const SAMPLE_BOUND: i64 = 10;
+
+/// A xorshift pseudo-random number generator, seeded from the system time
+pub(crate) struct Sampler(u64);
+
+impl Sampler {
+    pub(crate) fn new() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        // The state of xorshift must not be zero
+        Sampler(nanos | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `-bound..=bound`
+    pub(crate) fn next_in(&mut self, bound: i64) -> i64 {
+        (self.next_u64() % (2 * bound as u64 + 1)) as i64 - bound
+    }
+
+    /// A value in `0..n`
+    pub(crate) fn next_below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A state that assigns a random value to each of `vars`, and to the entries of `arrays` at the indices
+    /// `-bound..=bound`
+    pub(crate) fn next_state(&mut self, vars: &[Var], arrays: &[Var], bound: i64) -> State {
+        let mut state = State::new();
+        for var in vars {
+            state.update(var, self.next_in(bound));
+        }
+        for arr in arrays {
+            for index in -bound..=bound {
+                state.update_array(arr, index, self.next_in(bound));
+            }
+        }
+        state
+    }
+}
@@ -1,66 +1,91 @@
 use super::state::*;
 use super::ast::*;
 use super::expression::{arithmetic_eval, boolean_eval};
+use super::Result;
+use std::collections::HashMap;
+use super::error::ImpErrors;
 
 pub struct SOS{
     config: Configuration,
+    funcdefs: HashMap<String, ImpFuncDef>,
+    error: Option<ImpErrors>,
     done: bool,
 }
 
 impl SOS {
-    pub fn new(config: Configuration) -> Self {
-        SOS {config, done: false}
+    pub fn new(config: Configuration, funcdefs: HashMap<String, ImpFuncDef>) -> Self {
+        SOS {config, funcdefs, error: None, done: false}
     }
 
-    pub fn run_execution(&mut self) -> Configuration {
+    pub fn run_execution(&mut self) -> Result<Configuration> {
         let mut it = self.peekable();
         print!("   ");
-        while it.peek().is_some() && it.peek().unwrap().is_nonterminal()  {
-            print!("{:?}\n-> ", it.next().unwrap())
+        while let Some(Ok(config)) = it.peek() {
+            if !config.is_nonterminal() {
+                break;
+            }
+            print!("{:?}\n-> ", it.next().unwrap()?)
         }
-        // The iterator never stops unless its a Terminal state, hence the next iterator
-        // Option<Configuration> should be Some(Terminal)
+        // The iterator never stops unless it reaches a final configuration or an error occurred, hence
+        // the next iterator Option<Result<Configuration>> should be Some(Ok(final)) or Some(Err(_))
         let term = it.next().unwrap();
-        println!("{:?}", term);
+        match &term {
+            Ok(config) => println!("{:?}", config),
+            Err(_) => println!("<error>"),
+        }
         term
     }
 }
 
 impl Iterator for SOS {
-    type Item = Configuration;
+    type Item = Result<Configuration>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.done {
             return None;
         }
 
+        if let Some(err) = self.error.take() {
+            self.done = true;
+            return Some(Err(err));
+        }
+
         let old_config = self.config.clone();
 
-        if let Configuration::Terminal(_) = self.config {
+        if !self.config.is_nonterminal() {
             self.done = true;
         } else {
-            self.config = transition(self.config.clone());
+            match transition(self.config.clone(), &self.funcdefs) {
+                Ok(config) => self.config = config,
+                // Yield the configuration whose transition failed first, the error afterwards
+                Err(err) => self.error = Some(err),
+            }
         }
 
 
-        return Some(old_config);
+        return Some(Ok(old_config));
     }
 }
 
-pub fn transition(initial: Configuration) -> Configuration {
+pub fn transition(initial: Configuration, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<Configuration> {
     let (stm, mut initial_state) = match initial {
-        Configuration::Terminal(s) => return Configuration::Terminal(s),
         Configuration::Nonterminal(stm, s) => (stm, s),
+        other => return Ok(other),
     };
 
-    match *stm.clone() {
+    Ok(match *stm.clone() {
         Stm::Skip => Configuration::Terminal(initial_state),
         Stm::Assign(x, e) => {
-            initial_state.update(&x, arithmetic_eval(&e, &initial_state));
+            initial_state.update(&x, arithmetic_eval(&e, &initial_state, funcdefs)?);
+            Configuration::Terminal(initial_state)
+        },
+        Stm::ArrAssign(a, i, e) => {
+            let index = arithmetic_eval(&i, &initial_state, funcdefs)?;
+            initial_state.update_array(&a, index, arithmetic_eval(&e, &initial_state, funcdefs)?);
             Configuration::Terminal(initial_state)
         },
         Stm::Seq(stm1, stm2) => {
-            let config1 = transition(Configuration::Nonterminal(stm1, initial_state));
+            let config1 = transition(Configuration::Nonterminal(stm1, initial_state), funcdefs)?;
             match config1 {
                 Configuration::Terminal(state1) => Configuration::Nonterminal(stm2, state1),
                 Configuration::Nonterminal(stm11, state1) => {
@@ -69,10 +94,13 @@ pub fn transition(initial: Configuration) -> Configuration {
                         state1
                     )
                 },
+                // A sequence whose first statement is stuck is stuck as a whole
+                Configuration::Stuck(_, state1) => Configuration::Stuck(stm, state1),
+                other => other,
             }
         },
         Stm::If(cond, stm_then, stm_else) => {
-            if boolean_eval(&cond, &initial_state) {
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
                 Configuration::Nonterminal(stm_then, initial_state)
             } else {
                 Configuration::Nonterminal(stm_else, initial_state)
@@ -91,5 +119,49 @@ pub fn transition(initial: Configuration) -> Configuration {
                 initial_state
             )
         },
-    }
+        Stm::Assert(cond) => {
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                Configuration::Terminal(initial_state)
+            } else {
+                Configuration::Failed(*cond, initial_state)
+            }
+        },
+        Stm::Assume(cond) => {
+            if boolean_eval(&cond, &initial_state, funcdefs)? {
+                Configuration::Terminal(initial_state)
+            } else {
+                Configuration::Blocked(*cond, initial_state)
+            }
+        },
+        // There is no transition for abort
+        Stm::Abort => Configuration::Stuck(stm, initial_state),
+        Stm::Repeat(stm_inner, cond) => {
+            Configuration::Nonterminal(
+                Box::new(Stm::Seq(
+                    stm_inner.clone(),
+                    Box::new(Stm::If(
+                        cond.clone(),
+                        Box::new(Stm::Skip),
+                        Box::new(Stm::Repeat(stm_inner, cond)),
+                    )),
+                )),
+                initial_state
+            )
+        },
+        Stm::For(x, from, to, stm_inner) => {
+            // Both bounds are evaluated once, before the first iteration
+            let from = arithmetic_eval(&from, &initial_state, funcdefs)?;
+            let to = arithmetic_eval(&to, &initial_state, funcdefs)?;
+            initial_state.update(&x, from);
+
+            if from <= to {
+                Configuration::Nonterminal(
+                    Box::new(Stm::Seq(stm_inner.clone(), Stm::next_for_iteration(x, to, stm_inner))),
+                    initial_state
+                )
+            } else {
+                Configuration::Terminal(initial_state)
+            }
+        },
+    })
 }
\ No newline at end of file
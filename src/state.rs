@@ -1,31 +1,35 @@
-use super::ast::{Stm, Var};
+use super::ast::{Bexp, Stm, Var};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub enum Configuration {
     Terminal(State),
     Nonterminal(Box<Stm>, State),
+    /// An `assert` whose condition did not hold
+    Failed(Bexp, State),
+    /// An `assume` whose condition did not hold, i.e. the execution path is blocked
+    Blocked(Bexp, State),
+    /// A statement without any transition or derivation, i.e. one that executes `abort`
+    Stuck(Box<Stm>, State),
 }
 
 impl Configuration {
     pub fn is_terminal(&self) -> bool {
-        match self {
-            Configuration::Terminal(_) => true,
-            Configuration::Nonterminal(_, _) => false,
-        }
+        matches!(self, Configuration::Terminal(_))
     }
 
     pub fn is_nonterminal(&self) -> bool {
-        !self.is_terminal()
+        matches!(self, Configuration::Nonterminal(_, _))
     }
 }
 
+/// Integer variables and arrays, unassigned variables and array entries are `0`
 #[derive(Debug, Clone)]
-pub struct State(HashMap<Var, i64>);
+pub struct State(HashMap<Var, i64>, HashMap<Var, HashMap<i64, i64>>);
 
 impl State {
     pub fn new() -> Self {
-        State(HashMap::new())
+        State(HashMap::new(), HashMap::new())
     }
 
     pub fn update(&mut self, v: &Var, val: i64) {
@@ -35,4 +39,32 @@ impl State {
     pub fn get(&self, v: &Var) -> i64 {
         *self.0.get(v).unwrap_or(&0)
     }
+
+    /// The assigned integer variables
+    pub fn vars(&self) -> &HashMap<Var, i64> {
+        &self.0
+    }
+
+    /// The assigned array entries by array and index
+    pub fn arrays(&self) -> &HashMap<Var, HashMap<i64, i64>> {
+        &self.1
+    }
+
+    /// The assigned integer variables and the non-zero array entries, where the entry at index `i` of `a` is
+    /// named `a[i]`
+    pub fn entries(&self) -> HashMap<Var, i64> {
+        let mut entries = self.0.clone();
+        for (a, arr) in &self.1 {
+            entries.extend(arr.iter().filter(|(_, val)| **val != 0).map(|(index, val)| (format!("{}[{}]", a, index), *val)));
+        }
+        entries
+    }
+
+    pub fn update_array(&mut self, a: &Var, index: i64, val: i64) {
+        self.1.entry(a.to_string()).or_default().insert(index, val);
+    }
+
+    pub fn get_array(&self, a: &Var, index: i64) -> i64 {
+        self.1.get(a).and_then(|arr| arr.get(&index)).copied().unwrap_or(0)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,285 @@
+use super::ast::*;
+use super::axiomatic::{build_funcmap, state_of_model};
+use super::error::string_of_model;
+use super::state::State;
+use std::collections::{HashMap, HashSet};
+use z3::ast::Bool;
+use z3::{RecFuncDecl, SatResult};
+
+use super::Result;
+
+/// How an execution path ends
+#[derive(Clone, Debug)]
+pub enum PathEnd {
+    Terminal,
+    /// An `assert` whose condition does not hold
+    Failed(Bexp),
+    /// The path executes `abort`
+    Stuck,
+    /// A loop is still running after the maximum number of iterations
+    Unfinished,
+}
+
+/// A feasible execution path of a program
+#[derive(Clone, Debug)]
+pub struct SymPath {
+    /// The conditions on the initial values of the variables under which the path is taken
+    pub condition: Vec<Bexp>,
+    /// The values of the assigned variables at the end of the path, in terms of the initial values
+    pub values: Vec<(Var, Aexp)>,
+    /// The entries of the assigned arrays at the end of the path
+    pub arrays: Vec<(Var, Array)>,
+    pub end: PathEnd,
+    /// Initial values of the variables and array entries that take the path, if Z3 finds them
+    pub input: Option<State>,
+}
+
+impl SymPath {
+    /// `bexp` over the values at the end of the path, i.e. in terms of the initial values
+    pub fn at_end(&self, bexp: &Bexp) -> Bexp {
+        let state = SymState {
+            vars: self.values.iter().cloned().collect(),
+            arrays: self.arrays.iter().cloned().collect(),
+            condition: vec![],
+        };
+        Executor::sym_bexp(&state, bexp)
+    }
+
+    pub fn indent_string(&self, prefix: String) -> String {
+        let end = match &self.end {
+            PathEnd::Terminal => "terminates".to_owned(),
+            PathEnd::Failed(assertion) => format!("fails the assertion {:?}", assertion),
+            PathEnd::Stuck => "aborts".to_owned(),
+            PathEnd::Unfinished => "exceeds the maximum number of loop iterations".to_owned(),
+        };
+        let condition = if self.condition.is_empty() {
+            "true".to_owned()
+        } else {
+            self.condition.iter().map(|cond| format!("{:?}", cond)).collect::<Vec<_>>().join(" and ")
+        };
+        let input = match &self.input {
+            Some(input) => string_of_model(&input.entries()),
+            None => "unknown".to_owned(),
+        };
+        let mut values: Vec<String> = self.values.iter().map(|(v, aexp)| format!("{} = {:?}", v, aexp)).collect();
+        values.extend(self.arrays.iter().map(|(a, arr)| format!("{} = {:?}", a, arr)));
+
+        format!(
+            "{}The path {}\n{}    condition: {}\n{}    input: {}\n{}    values: {}",
+            prefix, end, prefix, condition, prefix, input, prefix, values.join(", ")
+        )
+    }
+}
+
+/// The values of the variables and arrays in terms of their initial values, and the path condition
+#[derive(Clone)]
+struct SymState {
+    vars: HashMap<Var, Aexp>,
+    arrays: HashMap<Var, Array>,
+    condition: Vec<Bexp>,
+}
+
+struct Executor<'ctx> {
+    ctx: &'ctx z3::Context,
+    funcmap: HashMap<String, RecFuncDecl<'ctx>>,
+    max_iterations: usize,
+    finished: Vec<(SymState, PathEnd)>,
+}
+
+impl<'ctx> Executor<'ctx> {
+    // Primed variables cannot occur in the source, so all variables and arrays are renamed to primed temporaries
+    // first, since their values refer to the initial values of each other
+    fn sym_aexp(state: &SymState, aexp: &Aexp) -> Aexp {
+        let renamed = state.vars.keys().fold(aexp.clone(), |aexp, v| aexp.substitute(v, &Aexp::Var(format!("{}'", v))));
+        let renamed = state.arrays.keys().fold(renamed, |aexp, a| aexp.substitute_array(a, &Array::Var(format!("{}'", a))));
+
+        let substituted = state.vars.iter().fold(renamed, |aexp, (v, val)| aexp.substitute(&format!("{}'", v), val));
+        state.arrays.iter().fold(substituted, |aexp, (a, arr)| aexp.substitute_array(&format!("{}'", a), arr))
+    }
+
+    fn sym_bexp(state: &SymState, bexp: &Bexp) -> Bexp {
+        let renamed = state.vars.keys().fold(bexp.clone(), |bexp, v| bexp.substitute(v, &Aexp::Var(format!("{}'", v))));
+        let renamed = state.arrays.keys().fold(renamed, |bexp, a| bexp.substitute_array(a, &Array::Var(format!("{}'", a))));
+
+        let substituted = state.vars.iter().fold(renamed, |bexp, (v, val)| bexp.substitute(&format!("{}'", v), val));
+        state.arrays.iter().fold(substituted, |bexp, (a, arr)| bexp.substitute_array(&format!("{}'", a), arr))
+    }
+
+    fn sym_array(state: &SymState, a: &Var) -> Array {
+        state.arrays.get(a).cloned().unwrap_or(Array::Var(a.clone()))
+    }
+
+    /// Whether the path condition is satisfiable, paths are only pruned if Z3 proves that they are infeasible
+    fn is_feasible(&self, condition: &[Bexp]) -> bool {
+        let solver = z3::Solver::new(self.ctx);
+        for cond in condition {
+            solver.assert(&cond.to_z3_bool(self.ctx, &self.funcmap));
+        }
+        solver.check() != SatResult::Unsat
+    }
+
+    /// The state continuing with the symbolic condition `cond`, if that path is feasible
+    fn branch(&self, state: &SymState, cond: Bexp) -> Option<SymState> {
+        let mut next = state.clone();
+        next.condition.push(cond);
+        if self.is_feasible(&next.condition) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// All states in which `stm` terminates normally when started in `state`, other paths are finished
+    fn exec(&mut self, stm: &Stm, state: SymState) -> Vec<SymState> {
+        match stm {
+            Stm::Skip => vec![state],
+            Stm::Assign(x, e) => {
+                let mut next = state.clone();
+                next.vars.insert(x.clone(), Self::sym_aexp(&state, e));
+                vec![next]
+            },
+            Stm::ArrAssign(a, i, e) => {
+                let mut next = state.clone();
+                let arr = Array::Store(
+                    Box::new(Self::sym_array(&state, a)),
+                    Box::new(Self::sym_aexp(&state, i)),
+                    Box::new(Self::sym_aexp(&state, e)),
+                );
+                next.arrays.insert(a.clone(), arr);
+                vec![next]
+            },
+            Stm::Seq(stm1, stm2) => {
+                self.exec(stm1, state).into_iter().flat_map(|state1| self.exec(stm2, state1)).collect()
+            },
+            Stm::If(cond, stm_then, stm_else) => {
+                let cond = Self::sym_bexp(&state, cond);
+                let mut res = vec![];
+                if let Some(then_state) = self.branch(&state, cond.clone()) {
+                    res.extend(self.exec(stm_then, then_state));
+                }
+                if let Some(else_state) = self.branch(&state, Bexp::Not(Box::new(cond))) {
+                    res.extend(self.exec(stm_else, else_state));
+                }
+                res
+            },
+            Stm::While(cond, stm_inner) => self.exec_while(cond, stm_inner, state, 0),
+            Stm::Assert(cond) => {
+                let sym_cond = Self::sym_bexp(&state, cond);
+                if let Some(failed_state) = self.branch(&state, Bexp::Not(Box::new(sym_cond.clone()))) {
+                    self.finished.push((failed_state, PathEnd::Failed(*cond.clone())));
+                }
+                self.branch(&state, sym_cond).into_iter().collect()
+            },
+            // The paths where the condition does not hold are blocked, i.e. not executed at all
+            Stm::Assume(cond) => self.branch(&state, Self::sym_bexp(&state, cond)).into_iter().collect(),
+            Stm::Abort => {
+                self.finished.push((state, PathEnd::Stuck));
+                vec![]
+            },
+            Stm::Repeat(stm_inner, cond) => self.exec_repeat(stm_inner, cond, state, 0),
+            Stm::For(x, from, to, stm_inner) => {
+                // Both bounds are evaluated once, before the first iteration
+                let to = Self::sym_aexp(&state, to);
+                let mut next = state.clone();
+                next.vars.insert(x.clone(), Self::sym_aexp(&state, from));
+                self.exec_for(x, &to, stm_inner, next, 0)
+            },
+        }
+    }
+
+    fn exec_while(&mut self, cond: &Bexp, stm_inner: &Stm, state: SymState, iteration: usize) -> Vec<SymState> {
+        let sym_cond = Self::sym_bexp(&state, cond);
+        let mut res: Vec<SymState> = self.branch(&state, Bexp::Not(Box::new(sym_cond.clone()))).into_iter().collect();
+
+        if let Some(inner_state) = self.branch(&state, sym_cond) {
+            if iteration == self.max_iterations {
+                self.finished.push((inner_state, PathEnd::Unfinished));
+            } else {
+                for state1 in self.exec(stm_inner, inner_state) {
+                    res.extend(self.exec_while(cond, stm_inner, state1, iteration + 1));
+                }
+            }
+        }
+
+        res
+    }
+
+    fn exec_repeat(&mut self, stm_inner: &Stm, cond: &Bexp, state: SymState, iteration: usize) -> Vec<SymState> {
+        if iteration == self.max_iterations {
+            self.finished.push((state, PathEnd::Unfinished));
+            return vec![];
+        }
+
+        let mut res = vec![];
+        for state1 in self.exec(stm_inner, state) {
+            let sym_cond = Self::sym_bexp(&state1, cond);
+            if let Some(again_state) = self.branch(&state1, Bexp::Not(Box::new(sym_cond.clone()))) {
+                res.extend(self.exec_repeat(stm_inner, cond, again_state, iteration + 1));
+            }
+            res.extend(self.branch(&state1, sym_cond));
+        }
+
+        res
+    }
+
+    fn exec_for(&mut self, x: &Var, to: &Aexp, stm_inner: &Stm, state: SymState, iteration: usize) -> Vec<SymState> {
+        let sym_cond = Bexp::Rop(Box::new(state.vars[x].clone()), Ropcode::Le, Box::new(to.clone()));
+        let mut res: Vec<SymState> = self.branch(&state, Bexp::Not(Box::new(sym_cond.clone()))).into_iter().collect();
+
+        if let Some(inner_state) = self.branch(&state, sym_cond) {
+            if iteration == self.max_iterations {
+                self.finished.push((inner_state, PathEnd::Unfinished));
+            } else {
+                for mut state1 in self.exec(stm_inner, inner_state) {
+                    let next = Aexp::Op(Box::new(state1.vars[x].clone()), Opcode::Add, Box::new(Aexp::Numeral(1)));
+                    state1.vars.insert(x.clone(), next);
+                    res.extend(self.exec_for(x, to, stm_inner, state1, iteration + 1));
+                }
+            }
+        }
+
+        res
+    }
+
+    fn path_of(&self, state: SymState, end: PathEnd) -> SymPath {
+        let mut vars: HashSet<Var> = state.condition.iter().flat_map(|cond| cond.free_vars()).collect();
+        vars.extend(state.vars.values().flat_map(|aexp| aexp.free_vars()));
+        vars.extend(state.arrays.values().flat_map(|arr| arr.free_vars()));
+        let mut reads: Vec<(Var, Aexp)> = state.condition.iter().flat_map(|cond| cond.array_reads()).collect();
+        reads.extend(state.vars.values().flat_map(|aexp| aexp.array_reads()));
+
+        let solver = z3::Solver::new(self.ctx);
+        solver.assert(&Bool::and(self.ctx, &state.condition.iter()
+            .map(|cond| cond.to_z3_bool(self.ctx, &self.funcmap))
+            .collect::<Vec<_>>()
+            .iter()
+            .collect::<Vec<_>>()));
+        let input = match solver.check() {
+            SatResult::Sat => Some(state_of_model(self.ctx, &self.funcmap, solver.get_model().unwrap(), vars, reads)),
+            _ => None,
+        };
+
+        let mut values: Vec<(Var, Aexp)> = state.vars.into_iter().collect();
+        values.sort_by(|(v1, _), (v2, _)| v1.cmp(v2));
+        let mut arrays: Vec<(Var, Array)> = state.arrays.into_iter().collect();
+        arrays.sort_by(|(a1, _), (a2, _)| a1.cmp(a2));
+
+        SymPath { condition: state.condition, values, arrays, end, input }
+    }
+}
+
+/// Executes `stm` symbolically, forking at every branch whose both directions are feasible.
+/// Loops are unrolled at most `max_iterations` times. Returns all feasible paths with an initial state taking each.
+pub fn explore(cfg: &z3::Config, stm: &Stm, funcdefs: &HashMap<String, ImpFuncDef>, max_iterations: usize) -> Result<Vec<SymPath>> {
+    let ctx = z3::Context::new(cfg);
+    let funcmap = build_funcmap(&ctx, funcdefs)?;
+    let mut executor = Executor { ctx: &ctx, funcmap, max_iterations, finished: vec![] };
+
+    let initial = SymState { vars: HashMap::new(), arrays: HashMap::new(), condition: vec![] };
+    for state in executor.exec(stm, initial) {
+        executor.finished.push((state, PathEnd::Terminal));
+    }
+
+    let finished = std::mem::take(&mut executor.finished);
+    Ok(finished.into_iter().map(|(state, end)| executor.path_of(state, end)).collect())
+}
@@ -0,0 +1,285 @@
+use super::ast::*;
+use super::error::ImpErrorInner::*;
+use crate::error::{err_acc, ImpErrors};
+use std::collections::{HashMap, HashSet};
+use z3::SatResult;
+
+use super::Result;
+
+/// The recursive calls of `f` in `aexp` as `(guards, args)`, where `guards` are the conditions of the
+/// enclosing `? :` expressions and the left operands of `and` and `or` that lead to the call
+fn guarded_calls_aexp(f: &str, aexp: &Aexp, guards: &Vec<Bexp>, calls: &mut Vec<(Vec<Bexp>, Vec<Aexp>)>) {
+    match aexp {
+        Aexp::Numeral(_) | Aexp::Var(_) => (),
+        Aexp::Op(left, _, right) => {
+            guarded_calls_aexp(f, left, guards, calls);
+            guarded_calls_aexp(f, right, guards, calls);
+        },
+        Aexp::Neg(inner) => guarded_calls_aexp(f, inner, guards, calls),
+        Aexp::Select(arr, index) => {
+            guarded_calls_array(f, arr, guards, calls);
+            guarded_calls_aexp(f, index, guards, calls);
+        },
+        Aexp::FuncApp(fname, args) => {
+            args.iter().for_each(|arg| guarded_calls_aexp(f, arg, guards, calls));
+            if fname == f {
+                calls.push((guards.clone(), args.clone()));
+            }
+        },
+        Aexp::Ite(cond, t, e) => {
+            guarded_calls_bexp(f, cond, guards, calls);
+
+            let mut then_guards = guards.clone();
+            then_guards.push(*cond.clone());
+            guarded_calls_aexp(f, t, &then_guards, calls);
+
+            let mut else_guards = guards.clone();
+            else_guards.push(Bexp::Not(cond.clone()));
+            guarded_calls_aexp(f, e, &else_guards, calls);
+        },
+    }
+}
+
+fn guarded_calls_array(f: &str, arr: &Array, guards: &Vec<Bexp>, calls: &mut Vec<(Vec<Bexp>, Vec<Aexp>)>) {
+    if let Array::Store(arr, index, value) = arr {
+        guarded_calls_array(f, arr, guards, calls);
+        guarded_calls_aexp(f, index, guards, calls);
+        guarded_calls_aexp(f, value, guards, calls);
+    }
+}
+
+fn guarded_calls_bexp(f: &str, bexp: &Bexp, guards: &Vec<Bexp>, calls: &mut Vec<(Vec<Bexp>, Vec<Aexp>)>) {
+    match bexp {
+        Bexp::Rop(left, _, right) => {
+            guarded_calls_aexp(f, left, guards, calls);
+            guarded_calls_aexp(f, right, guards, calls);
+        },
+        // The right operand only matters if the left one does not determine the result, i.e. the definition
+        // is well-founded if the calls are evaluated from left to right
+        Bexp::Bop(left, bop, right) => {
+            guarded_calls_bexp(f, left, guards, calls);
+
+            let mut right_guards = guards.clone();
+            match bop {
+                Bopcode::And => right_guards.push(*left.clone()),
+                Bopcode::Or => right_guards.push(Bexp::Not(left.clone())),
+            }
+            guarded_calls_bexp(f, right, &right_guards, calls);
+        },
+        Bexp::Not(inner) => guarded_calls_bexp(f, inner, guards, calls),
+        // The bound variable is renamed apart from the parameters, which it may shadow, so that the calls in the
+        // body have to decrease for every value of it
+        Bexp::Quant(_, x, body) => {
+            let body = body.clone().substitute(x, &Aexp::Var(format!("{}!bound", x)));
+            guarded_calls_bexp(f, &body, guards, calls)
+        },
+        Bexp::PredApp(pname, args) => {
+            args.iter().for_each(|arg| guarded_calls_aexp(f, arg, guards, calls));
+            if pname == f {
+                calls.push((guards.clone(), args.clone()));
+            }
+        },
+    }
+}
+
+/// Whether `f` calls itself through other functions
+fn is_indirectly_recursive(f: &ImpFuncDef, funcdefs: &HashMap<String, ImpFuncDef>) -> bool {
+    let mut visited = HashSet::new();
+    let mut todo: Vec<String> = f.func_apps().into_iter().map(|(g, _)| g).filter(|g| *g != f.name).collect();
+
+    while let Some(g) = todo.pop() {
+        if g == f.name {
+            return true;
+        }
+        if !visited.insert(g.clone()) {
+            continue;
+        }
+        if let Some(gdef) = funcdefs.get(&g) {
+            todo.extend(gdef.func_apps().into_iter().map(|(h, _)| h));
+        }
+    }
+
+    false
+}
+
+/// Whether `0 <= measure and measure[params ↦ args] < measure` holds under the guards of every recursive call.
+/// All functions are uninterpreted, since their definitions are only admitted after this check.
+fn is_decreasing(cfg: &z3::Config, f: &ImpFuncDef, funcdefs: &HashMap<String, ImpFuncDef>, measure: &Aexp, calls: &[(Vec<Bexp>, Vec<Aexp>)]) -> bool {
+    let ctx = z3::Context::new(cfg);
+    let funcmap: HashMap<_, _> = funcdefs.iter().map(|(k, v)| (k.clone(), v.to_z3_func_decl(&ctx))).collect();
+
+    calls.iter().all(|(guards, args)| {
+        let substitution: Vec<(Var, Aexp)> = f.args.iter().cloned().zip(args.iter().cloned()).collect();
+        let next_measure = measure.clone().substitute_all(&substitution);
+        let decreasing = Bexp::Bop(
+            Box::new(Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Le, Box::new(measure.clone()))),
+            Bopcode::And,
+            Box::new(Bexp::Rop(Box::new(next_measure), Ropcode::Lt, Box::new(measure.clone()))),
+        );
+
+        let solver = z3::Solver::new(&ctx);
+        for guard in guards {
+            solver.assert(&guard.to_z3_bool(&ctx, &funcmap));
+        }
+        solver.assert(&decreasing.to_z3_bool(&ctx, &funcmap).not());
+
+        solver.check() == SatResult::Unsat
+    })
+}
+
+/// Whether `bexp` is satisfiable, where all functions are uninterpreted
+fn is_satisfiable(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>, bexp: &Bexp) -> bool {
+    let ctx = z3::Context::new(cfg);
+    let funcmap: HashMap<_, _> = funcdefs.iter().map(|(k, v)| (k.clone(), v.to_z3_func_decl(&ctx))).collect();
+
+    let solver = z3::Solver::new(&ctx);
+    solver.assert(&bexp.to_z3_bool(&ctx, &funcmap));
+
+    solver.check() != SatResult::Unsat
+}
+
+/// Checks that the guards of a definition by clauses cover every argument and that no two guards overlap,
+/// so that the order of the clauses does not matter
+fn verify_clauses(cfg: &z3::Config, f: &ImpFuncDef, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+    let clauses = match &f.body {
+        ImpFuncBody::Clauses(clauses) => clauses,
+        _ => return Ok(()),
+    };
+
+    if clauses.iter().rev().skip(1).any(|clause| clause.guard.is_none()) {
+        return Err(Other(format!("Function {}: only the last clause may be `otherwise`", f.name)).into());
+    }
+
+    let guards: Vec<&Bexp> = clauses.iter().filter_map(|clause| clause.guard.as_ref()).collect();
+    let mut errs = ImpErrors(vec![]);
+
+    if guards.len() == clauses.len() {
+        let uncovered = guards.iter().fold(
+            Bexp::Rop(Box::new(Aexp::Numeral(0)), Ropcode::Eq, Box::new(Aexp::Numeral(0))),
+            |acc, guard| Bexp::Bop(Box::new(acc), Bopcode::And, Box::new(Bexp::Not(Box::new((*guard).clone())))),
+        );
+        if is_satisfiable(cfg, funcdefs, &uncovered) {
+            errs.0.push(Other(format!(
+                "Function {}: the guards of the clauses are not exhaustive, add a clause `| otherwise = ...`", f.name
+            )));
+        }
+    }
+
+    for (i, first) in guards.iter().enumerate() {
+        for second in &guards[i + 1..] {
+            let overlap = Bexp::Bop(Box::new((*first).clone()), Bopcode::And, Box::new((*second).clone()));
+            if is_satisfiable(cfg, funcdefs, &overlap) {
+                errs.0.push(Other(format!(
+                    "Function {}: the guards {:?} and {:?} overlap", f.name, first, second
+                )));
+            }
+        }
+    }
+
+    if !errs.0.is_empty() {
+        return Err(errs);
+    }
+
+    Ok(())
+}
+
+fn verify_termination(cfg: &z3::Config, f: &ImpFuncDef, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+    if is_indirectly_recursive(f, funcdefs) {
+        return Err(Other(format!(
+            "Function {} calls itself through other functions, only direct recursion is supported", f.name
+        )).into());
+    }
+
+    let mut calls = vec![];
+    match &f.body {
+        ImpFuncBody::Bool(body) => guarded_calls_bexp(&f.name, body, &vec![], &mut calls),
+        _ => guarded_calls_aexp(&f.name, &f.int_body().unwrap(), &vec![], &mut calls),
+    }
+    if calls.is_empty() {
+        return Ok(());
+    }
+
+    // Without an annotation, each parameter is tried as the measure
+    let candidates: Vec<Aexp> = match &f.decreases {
+        Some(measure) => vec![measure.clone()],
+        None => f.args.iter().map(|arg| Aexp::Var(arg.clone())).collect(),
+    };
+
+    println!("Verifying termination of {}:", f.name);
+    match candidates.iter().find(|measure| is_decreasing(cfg, f, funcdefs, measure, &calls)) {
+        Some(measure) => {
+            println!("Verified with measure {:?}.", measure);
+            Ok(())
+        },
+        None => {
+            println!("ERROR! Couldn't find a decreasing measure.");
+            let msg = match &f.decreases {
+                Some(measure) => format!(
+                    "Could not prove that {} terminates: the measure {:?} must be non-negative and decrease for every recursive call",
+                    f.name, measure
+                ),
+                None => format!(
+                    "Could not prove that {} terminates: no parameter decreases for every recursive call, annotate a measure with `decreases`",
+                    f.name
+                ),
+            };
+            Err(Other(msg).into())
+        },
+    }
+}
+
+/// Checks that the functions in `calls` and in the function bodies are defined, with the called number of arguments,
+/// and that integer functions and predicates are not called as each other, since Z3 could not encode these calls
+pub fn verify_calls(funcdefs: &HashMap<String, ImpFuncDef>, calls: Vec<(String, usize, bool)>) -> Result<()> {
+    let mut names: Vec<&String> = funcdefs.keys().collect();
+    names.sort();
+    let body_calls = names.into_iter().flat_map(|name| funcdefs[name].calls());
+
+    let mut errs = ImpErrors(vec![]);
+    let mut seen = HashSet::new();
+    for (name, arity, is_pred) in body_calls.chain(calls) {
+        if !seen.insert((name.clone(), arity, is_pred)) {
+            continue;
+        }
+        let msg = match funcdefs.get(&name) {
+            None => format!("Call of the undefined function {}", name),
+            Some(f) if f.args.len() != arity => {
+                format!("{} takes {} arguments, but is called with {}", name, f.args.len(), arity)
+            },
+            Some(f) => match (&f.body, is_pred) {
+                (ImpFuncBody::Bool(_), false) => format!("The predicate {} is called as an integer function", name),
+                (ImpFuncBody::Int(_) | ImpFuncBody::Clauses(_), true) => format!("The integer function {} is called as a predicate", name),
+                _ => continue,
+            },
+        };
+        errs.0.push(Other(msg));
+    }
+
+    if !errs.0.is_empty() {
+        return Err(errs);
+    }
+
+    Ok(())
+}
+
+/// Checks that every function is well-defined by clauses and terminates, since Z3 assumes the definitions of
+/// non-terminating functions as well, which may make the background theory inconsistent
+pub fn verify_funcdefs(cfg: &z3::Config, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<()> {
+    let mut errs = ImpErrors(vec![]);
+    let mut ea = |r: Result<()>| {
+        err_acc(&mut errs, r);
+    };
+
+    let mut names: Vec<&String> = funcdefs.keys().collect();
+    names.sort();
+    for name in names {
+        ea(verify_clauses(cfg, &funcdefs[name], funcdefs));
+        ea(verify_termination(cfg, &funcdefs[name], funcdefs));
+    }
+
+    if !errs.0.is_empty() {
+        return Err(errs);
+    }
+
+    Ok(())
+}
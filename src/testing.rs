@@ -0,0 +1,224 @@
+use super::ast::*;
+use super::axiomatic::{build_funcmap, map_of_model};
+use super::contracts::{block_arrays, block_vars, is_executable};
+use super::error::ImpErrorInner::*;
+use super::expression::boolean_eval;
+use super::sampling::{Sampler, MAX_STEPS, MAX_TRIES, SAMPLES, SAMPLE_BOUND};
+use super::small_step::SOS;
+use super::state::{Configuration, State};
+use std::collections::HashMap;
+use z3::ast::{Ast, Bool, Int};
+use z3::SatResult;
+
+use super::Result;
+
+enum Outcome {
+    Passed,
+    /// The violated assertion and the state at that point
+    Failed(Bexp, State),
+    /// The execution was blocked, aborted or did not terminate within `MAX_STEPS` steps
+    Inconclusive,
+}
+
+/// The program of an outline with its pre- and postcondition, all intermediate assertions are ignored
+struct Spec<'a> {
+    pre: Bexp,
+    stm: Box<Stm>,
+    post: Bexp,
+    funcdefs: &'a HashMap<String, ImpFuncDef>,
+}
+
+impl<'a> Spec<'a> {
+    fn new(prog: &AxBlock, funcdefs: &'a HashMap<String, ImpFuncDef>) -> Self {
+        let (pre, stm, post) = prog.spec();
+
+        Spec { pre, stm, post, funcdefs }
+    }
+
+    /// Whether `initial` satisfies the precondition, states generated by Z3 always do
+    fn admits(&self, initial: &State) -> Result<bool> {
+        if !is_executable(&self.pre) {
+            return Ok(true);
+        }
+        boolean_eval(&Box::new(self.pre.clone()), initial, self.funcdefs)
+    }
+
+    /// Runs the program from `initial`, the violated assertion is either the postcondition or an `assert` of the program
+    fn run(&self, initial: &State) -> Result<Outcome> {
+        let sos = SOS::new(Configuration::Nonterminal(self.stm.clone(), initial.clone()), self.funcdefs.clone());
+        for config in sos.take(MAX_STEPS) {
+            match config? {
+                Configuration::Nonterminal(_, _) => {},
+                Configuration::Terminal(state) => {
+                    return if boolean_eval(&Box::new(self.post.clone()), &state, self.funcdefs)? {
+                        Ok(Outcome::Passed)
+                    } else {
+                        Ok(Outcome::Failed(self.post.clone(), state))
+                    };
+                },
+                Configuration::Failed(assertion, state) => return Ok(Outcome::Failed(assertion, state)),
+                Configuration::Blocked(_, _) | Configuration::Stuck(_, _) => return Ok(Outcome::Inconclusive),
+            }
+        }
+
+        Ok(Outcome::Inconclusive)
+    }
+}
+
+/// Up to `count` distinct states satisfying `pre`, found by Z3. Each state is spread out by pinning a random
+/// variable to a random value, which is dropped again if the precondition excludes it.
+fn z3_states(cfg: &z3::Config, spec: &Spec, vars: &[Var], count: usize, sampler: &mut Sampler) -> Result<Vec<State>> {
+    let ctx = z3::Context::new(cfg);
+    let funcmap = build_funcmap(&ctx, spec.funcdefs)?;
+    let consts: Vec<Int> = vars.iter().map(|var| Int::new_const(&ctx, var.as_str())).collect();
+
+    let solver = z3::Solver::new(&ctx);
+    solver.assert(&spec.pre.to_z3_bool(&ctx, &funcmap));
+
+    let mut states = vec![];
+    while states.len() < count && !consts.is_empty() {
+        solver.push();
+        let pinned = &consts[sampler.next_below(consts.len())];
+        solver.assert(&pinned._eq(&Int::from_i64(&ctx, sampler.next_in(SAMPLE_BOUND))));
+        let pinned_model = if solver.check() == SatResult::Sat { solver.get_model() } else { None };
+        solver.pop(1);
+
+        let model = match pinned_model {
+            Some(model) => model,
+            None if solver.check() == SatResult::Sat => solver.get_model().unwrap(),
+            None => break,
+        };
+
+        let values = map_of_model(&ctx, model, vars.iter().cloned().collect());
+
+        // The same state is not generated twice
+        let eqs: Vec<Bool> = vars.iter().zip(&consts)
+            .map(|(var, c)| c._eq(&Int::from_i64(&ctx, values[var])))
+            .collect();
+        solver.assert(&Bool::and(&ctx, &eqs.iter().collect::<Vec<_>>()).not());
+
+        let mut state = State::new();
+        for (var, val) in values {
+            state.update(&var, val);
+        }
+        states.push(state);
+    }
+
+    Ok(states)
+}
+
+/// The values smaller than `val` that are tried when shrinking
+fn smaller_values(val: i64) -> Vec<i64> {
+    let mut smaller = vec![0, val / 2, val - val.signum()];
+    smaller.dedup();
+    smaller.into_iter().filter(|&new_val| new_val != val).collect()
+}
+
+/// Smaller variants of `state`, where a single variable or array entry is moved towards `0`
+fn shrink_candidates(state: &State) -> Vec<State> {
+    let mut vars: Vec<(&Var, &i64)> = state.vars().iter().collect();
+    vars.sort();
+    let mut entries: Vec<(&Var, i64, i64)> = state.arrays().iter()
+        .flat_map(|(a, arr)| arr.iter().map(move |(&index, &val)| (a, index, val)))
+        .collect();
+    entries.sort();
+
+    let mut candidates = vec![];
+    for (var, &val) in vars {
+        for new_val in smaller_values(val) {
+            let mut candidate = state.clone();
+            candidate.update(var, new_val);
+            candidates.push(candidate);
+        }
+    }
+    for (a, index, val) in entries {
+        for new_val in smaller_values(val) {
+            let mut candidate = state.clone();
+            candidate.update_array(a, index, new_val);
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+/// Shrinks a failing initial state as long as a smaller one satisfies the precondition and fails as well
+fn shrink(spec: &Spec, initial: State, violation: (Bexp, State)) -> (State, (Bexp, State)) {
+    let (mut initial, mut violation) = (initial, violation);
+
+    // Without evaluating the precondition, smaller states might not satisfy it
+    if !is_executable(&spec.pre) {
+        return (initial, violation);
+    }
+
+    'shrinking: loop {
+        for candidate in shrink_candidates(&initial) {
+            if let Ok(true) = spec.admits(&candidate) {
+                if let Ok(Outcome::Failed(assertion, state)) = spec.run(&candidate) {
+                    initial = candidate;
+                    violation = (assertion, state);
+                    continue 'shrinking;
+                }
+            }
+        }
+
+        return (initial, violation);
+    }
+}
+
+/// Runs the program from initial states satisfying the precondition and checks the postcondition.
+/// The initial states are sampled randomly, and generated by Z3 if too few of them satisfy a precondition without arrays.
+/// A failing initial state is shrunk to a minimal one and reported. Returns the number of passed executions,
+/// executions that do not terminate or end in a runtime error are inconclusive.
+pub fn test_spec(cfg: &z3::Config, prog: &AxBlock, funcdefs: &HashMap<String, ImpFuncDef>) -> Result<usize> {
+    let spec = Spec::new(prog, funcdefs);
+    if !is_executable(&spec.post) {
+        return Err(Other("The postcondition contains an unbounded quantifier, so it cannot be tested".to_owned()).into());
+    }
+
+    let mut vars: Vec<Var> = block_vars(prog).into_iter().collect();
+    vars.sort();
+    let mut arrays: Vec<Var> = block_arrays(prog).into_iter().collect();
+    arrays.sort();
+
+    let mut sampler = Sampler::new();
+    let mut initials = vec![];
+    if is_executable(&spec.pre) {
+        for _ in 0..MAX_TRIES {
+            if initials.len() == SAMPLES {
+                break;
+            }
+            let initial = sampler.next_state(&vars, &arrays, SAMPLE_BOUND);
+            // States whose precondition cannot be evaluated are left to Z3
+            if let Ok(true) = spec.admits(&initial) {
+                initials.push(initial);
+            }
+        }
+    }
+    // The entries of arrays are not taken from the models of Z3, so arrays are `0` in the states it generates
+    if initials.len() < SAMPLES && spec.pre.free_arrays().is_empty() {
+        let count = SAMPLES - initials.len();
+        initials.extend(z3_states(cfg, &spec, &vars, count, &mut sampler)?);
+    }
+
+    println!("Testing the specification with {} initial states...", initials.len());
+    let mut passed = 0;
+    for initial in initials {
+        match spec.run(&initial) {
+            Ok(Outcome::Passed) => passed += 1,
+            Ok(Outcome::Inconclusive) => {},
+            Ok(Outcome::Failed(assertion, state)) => {
+                let (initial, (assertion, state)) = shrink(&spec, initial, (assertion, state));
+                return Err(AssertionViolation {
+                    assertion,
+                    initial: initial.entries(),
+                    state: state.entries(),
+                }.into());
+            },
+            Err(errs) if errs.0.iter().all(|err| matches!(err, RuntimeError { .. })) => {},
+            Err(errs) => return Err(errs),
+        }
+    }
+
+    Ok(passed)
+}
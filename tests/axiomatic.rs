@@ -0,0 +1,177 @@
+use imp::ast::Bexp;
+use imp::error::ImpErrorInner;
+use imp::imp_lang::{AxProgramParser, BexpParser};
+use imp::{axiomatic, builtin_funcdefs, induction, run_ax_partial, run_ax_total};
+use std::collections::HashMap;
+use std::fs;
+
+fn entailment_fails(res: imp::Result<()>) -> bool {
+    matches!(res, Err(errs) if errs.0.iter().all(|err| matches!(err, ImpErrorInner::EntailmentError { .. })))
+}
+
+#[test]
+fn assert_and_assume_rules() {
+    let outline = "
+{ x > 0 }
+⊨
+{ x > 0 and x >= 0 }
+assert x > 0
+{ x >= 0 }
+assume x < 5
+{ x < 5 and x >= 0 }
+⊨
+{ x < 5 }
+";
+    run_ax_partial(outline).unwrap();
+    run_ax_total(outline).unwrap();
+
+    assert!(entailment_fails(run_ax_partial(&outline.replace("{ x > 0 }\n", "{ x >= 0 }\n"))));
+}
+
+#[test]
+fn abort_rules() {
+    // Partial correctness holds for any pre- and postcondition, total correctness requires the precondition false
+    let outline = "
+{ x = 0 }
+abort
+{ x = 1 }
+";
+    run_ax_partial(outline).unwrap();
+    assert!(matches!(
+        run_ax_total(outline),
+        Err(errs) if matches!(errs.0.as_slice(), [ImpErrorInner::AxStructureError { .. }])
+    ));
+    run_ax_total("{ 0 = 1 }\nabort\n{ x = 1 }").unwrap();
+}
+
+#[test]
+fn repeat_and_for_rules() {
+    let repeat = fs::read_to_string("examples/countdown_repeat_total.imp").unwrap();
+    run_ax_total(&repeat).unwrap();
+    assert!(entailment_fails(run_ax_total(&repeat.replace("{ c = X }", "{ c = X + 1 }"))));
+
+    let for_loop = fs::read_to_string("examples/multiply_for.imp").unwrap();
+    run_ax_partial(&for_loop).unwrap();
+    run_ax_total(&for_loop).unwrap();
+    assert!(entailment_fails(run_ax_partial(&for_loop.replace("{ s = n * k }", "{ s = n * k + 1 }"))));
+}
+
+#[test]
+fn array_assignment_rule() {
+    let swap = fs::read_to_string("examples/array_swap.imp").unwrap();
+    run_ax_partial(&swap).unwrap();
+    assert!(entailment_fails(run_ax_partial(&swap.replace("{ a[0] = x and a[1] = y }", "{ a[0] = x and a[1] = x + 1 }"))));
+}
+
+#[test]
+fn quantified_assertions() {
+    let max = fs::read_to_string("examples/array_max.imp").unwrap();
+    run_ax_partial(&max).unwrap();
+    assert!(entailment_fails(run_ax_partial(&max.replace("{ forall k. 0 <= k and k < n -> a[k] <= m }", "{ forall k. 0 <= k and k < n -> a[k] < m }"))));
+}
+
+#[test]
+fn axioms_are_assumed() {
+    let outline = "
+double(n) decreases n {
+    (n <= 0) ? 0 : double(n - 1) + 2
+}
+
+axiom doubleClosed: n >= 0 -> double(n) = 2 * n
+
+{ x >= 0 }
+⊨
+{ double(x) = 2 * x }
+skip
+{ double(x) = 2 * x }
+";
+    run_ax_partial(outline).unwrap();
+
+    let without_axiom: String = outline.lines().filter(|line| !line.starts_with("axiom")).collect::<Vec<_>>().join("\n");
+    assert!(entailment_fails(run_ax_partial(&without_axiom)));
+}
+
+#[test]
+fn lemmas_by_induction() {
+    let sum = fs::read_to_string("examples/sum_lemma.imp").unwrap();
+    run_ax_partial(&sum).unwrap();
+
+    // A lemma that does not hold is rejected, and is not assumed by the entailments
+    let bogus = sum.replace("2 * sum(n) = n * (n + 1)", "2 * sum(n) = n * n");
+    assert!(matches!(
+        run_ax_partial(&bogus),
+        Err(errs) if errs.0.iter().any(|err| matches!(err, ImpErrorInner::LemmaError { .. }))
+    ));
+
+    let (funcdefs_vec, lemmas, prog) = AxProgramParser::new().parse(&bogus).unwrap();
+    let mut funcdefs = builtin_funcdefs();
+    funcdefs.extend(funcdefs_vec.into_iter().map(|funcdef| (funcdef.name.clone(), funcdef)));
+    let mut cfg = z3::Config::new();
+    cfg.set_timeout_msec(5000);
+    let (proven, res) = induction::verify_lemmas(&cfg, &funcdefs, &lemmas);
+    assert!(res.is_err());
+    assert!(proven.is_empty());
+    assert!(entailment_fails(axiomatic::verify_cons_partial(&cfg, &prog, &funcdefs, &proven)));
+}
+
+/// A counterexample with the values of the conjuncts of the premise and the conclusion in it
+type Refutation = (HashMap<String, i64>, Vec<(Bexp, Option<bool>)>, Vec<(Bexp, Option<bool>)>);
+
+/// The refutations of the failing entailments of an outline
+fn refuted_entailments(src: &str) -> Vec<Refutation> {
+    let (funcdefs_vec, _, prog) = AxProgramParser::new().parse(src).unwrap();
+    let mut funcdefs = builtin_funcdefs();
+    funcdefs.extend(funcdefs_vec.into_iter().map(|funcdef| (funcdef.name.clone(), funcdef)));
+    let mut cfg = z3::Config::new();
+    cfg.set_timeout_msec(5000);
+
+    match axiomatic::verify_cons_partial(&cfg, &prog, &funcdefs, &[]) {
+        Ok(()) => vec![],
+        Err(errs) => errs.0.into_iter().map(|err| match err {
+            ImpErrorInner::EntailmentError { untrue_model: Some(model), src_conjuncts, dst_conjuncts, .. } => {
+                (model, src_conjuncts, dst_conjuncts)
+            },
+            err => panic!("unexpected error {:?}", err),
+        }).collect(),
+    }
+}
+
+#[test]
+fn counterexamples_are_minimized() {
+    let refuted = refuted_entailments("{ x > 100 and y < x }\n⊨\n{ x > 200 }\nskip\n{ x > 200 }\n");
+    assert_eq!(refuted.len(), 1);
+    let (model, _, _) = &refuted[0];
+    assert_eq!(model.get("x"), Some(&101));
+    assert_eq!(model.get("y"), Some(&0));
+}
+
+#[test]
+fn only_failing_conjuncts_are_refuted() {
+    let refuted = refuted_entailments("{ x > 0 and y = 1 }\n⊨\n{ x >= 0 and y = 2 and z = z }\nskip\n{ x >= 0 and y = 2 and z = z }\n");
+    assert_eq!(refuted.len(), 1);
+
+    let (_, src_conjuncts, dst_conjuncts) = &refuted[0];
+    assert!(src_conjuncts.iter().all(|(_, val)| *val == Some(true)));
+    assert_eq!(dst_conjuncts.len(), 1);
+    assert_eq!(dst_conjuncts[0], (BexpParser::new().parse("y = 2").map(|bexp| *bexp).unwrap(), Some(false)));
+}
+
+#[test]
+fn for_bound_must_not_mention_loop_variable() {
+    // The bound x + 5 is 5 when the loop starts, so the loop ends with x = 6 and not x = 1
+    let outline = "
+{ true }
+for x := 0 to x + 5 do
+    { x <= x + 5 and (true) }
+    skip
+    { true }
+end
+{ not x <= x + 5 and (true) }
+⊨
+{ 0 = 0 + 1 }
+";
+    assert!(matches!(
+        run_ax_partial(outline),
+        Err(errs) if errs.0.iter().any(|err| matches!(err, ImpErrorInner::Other(msg) if msg.contains("must not mention the loop variable")))
+    ));
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+/// Runs the axiomatic verifier on `src` with the extra arguments `args` and returns its output
+fn verify(name: &str, src: &str, args: &[&str]) -> String {
+    let path = env::temp_dir().join(format!("imp-cli-{}.imp", name));
+    fs::write(&path, src).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_imp"))
+        .arg(&path)
+        .args(["false", "false", "partial"])
+        .args(args)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn concretize_wrong_outline() {
+    let out = verify("wrong", "{ x >= 0 }\n⊨\n{ x - 1 >= 0 }\ny := x - 1\n{ y >= 0 }\n", &["--concretize"]);
+    assert!(out.contains("Running the statement from { x=0 } ends in the counterexample { x=0 }."), "{}", out);
+    assert!(out.contains("The outline is wrong: { x >= 0 } skip { x - 1 >= 0 } does not hold."), "{}", out);
+}
+
+#[test]
+fn concretize_weak_premise() {
+    let out = verify("weak", "{ 1 > 0 }\nx := 1\n{ x > 0 }\n⊨\n{ x = 1 }\n", &["--concretize"]);
+    assert!(out.contains("The assertion { x > 0 } is weaker than needed"), "{}", out);
+}
+
+#[test]
+fn concretize_loop_body() {
+    // The counterexample x=-1 satisfies the invariant, but no execution from the precondition reaches it
+    let src = "{ true }\nx := 0\n{ true }\nwhile x < 3 do\n    { x < 3 and true }\n    ⊨\n    { x + 1 >= 1 }\n    x := x + 1\n    { true }\nend\n{ not x < 3 and true }\n";
+    let out = verify("body", src, &["--concretize"]);
+    assert!(out.contains("The assertion { x < 3 and 0 = 0 } is weaker than needed"), "{}", out);
+    assert!(!out.contains("The outline is wrong"), "{}", out);
+}
+
+#[test]
+fn concretize_runtime_error() {
+    let out = verify("error", "{ x = 0 }\ny := 1 / x\n{ x = 0 }\n⊨\n{ y = 5 }\n", &["--concretize"]);
+    assert!(out.contains("Running the statement from { x=0 } fails: Runtime error: division by zero"), "{}", out);
+}
+
+#[test]
+fn refuted_entailments_are_printed() {
+    let out = verify("refuted", "{ x > 100 and y < x }\n⊨\n{ x > 200 }\nskip\n{ x > 200 }\n", &[]);
+    assert!(out.contains("ERROR: The following entailment is incorrect:"), "{}", out);
+    assert!(out.contains("Logical variables: { x=101, y=0 }"), "{}", out);
+}
+
+#[test]
+fn needed_conjuncts_are_explained() {
+    let out = verify("needed", "{ x = 0 and n >= 0 and y = 1 }\n⊨\n{ x + y = 1 }\nskip\n{ x + y = 1 }\n", &[]);
+    assert!(out.contains("The conclusion only needs these conjuncts of the premise: { x = 0 }, { y = 1 }"), "{}", out);
+    assert!(out.contains("The following conjuncts are not needed: { n >= 0 }"), "{}", out);
+}
+
+#[test]
+fn fix_keeps_a_backup() {
+    let path = env::temp_dir().join("imp-cli-fix.imp");
+    let backup = env::temp_dir().join("imp-cli-fix.imp.bak");
+    let original = "// keeps this comment\n{ x >= 0 }\ny := x + 1\n{ y > 0 }\n";
+    fs::write(&path, original).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_imp"))
+        .arg(&path)
+        .args(["false", "false", "partial", "--fix"])
+        .output()
+        .unwrap();
+    let out = String::from_utf8(output.stdout).unwrap();
+    let fixed = fs::read_to_string(&path).unwrap();
+    let saved = fs::read_to_string(&backup).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&backup).unwrap();
+
+    assert!(out.contains("Inserted 1 consequence steps"), "{}", out);
+    assert_eq!(saved, original);
+    assert_eq!(fixed, "// keeps this comment\n{ x >= 0 }\n⊨\n{ x + 1 > 0 }\ny := x + 1\n{ y > 0 }\n");
+    assert!(!out.contains("ERROR:"), "{}", out);
+}
@@ -0,0 +1,40 @@
+use imp::run_fix;
+
+#[test]
+fn fix_inserts_consequence_steps_into_source() {
+    let original = "// squares
+f(x) { x * x }
+{ n >= 0 }  // pre
+x := 0
+{ x = 0 and n >= 0 }
+while x < n do
+    /* body { not an assertion } */
+    { x < n and n >= 0 }
+    x := x + 1
+    { x <= n }
+end
+{ x = n }
+";
+    let expected = "// squares
+f(x) { x * x }
+{ n >= 0 }  // pre
+⊨
+{ 0 = 0 and n >= 0 }
+x := 0
+{ x = 0 and n >= 0 }
+while x < n do
+    /* body { not an assertion } */
+    { x < n and n >= 0 }
+    ⊨
+    { x + 1 <= n }
+    x := x + 1
+    { x <= n }
+end
+{ x = n }
+";
+
+    let (count, fixed) = run_fix(original).unwrap().unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(fixed, expected);
+    assert_eq!(run_fix(&fixed).unwrap(), None);
+}
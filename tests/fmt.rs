@@ -0,0 +1,48 @@
+use imp::imp_lang::{AxProgramParser, StmParser};
+use imp::run_fmt;
+use std::fs;
+
+fn assert_same_program(original: &str, formatted: &str, path: &str) {
+    match StmParser::new().parse(original) {
+        Ok(stm) => assert_eq!(stm, StmParser::new().parse(formatted).unwrap(), "{}", path),
+        Err(_) => assert_eq!(
+            AxProgramParser::new().parse(original).unwrap(),
+            AxProgramParser::new().parse(formatted).unwrap(),
+            "{}", path
+        ),
+    }
+}
+
+#[test]
+fn fmt_round_trips_examples() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let path = path.to_str().unwrap();
+        let original = fs::read_to_string(path).unwrap();
+
+        let formatted = run_fmt(&original).unwrap();
+        assert_same_program(&original, &formatted, path);
+        assert_eq!(formatted, run_fmt(&formatted).unwrap(), "{} is not formatted canonically", path);
+    }
+}
+
+#[test]
+fn fmt_keeps_sequences_nested_on_the_right() {
+    for original in &["x := 1; (y := 2; z := 3)", "(x := 1; y := 2); z := 3", "while x < 3 do x := x + 1; (y := x; z := y) end"] {
+        let formatted = run_fmt(original).unwrap();
+        assert_eq!(StmParser::new().parse(original).unwrap(), StmParser::new().parse(&formatted).unwrap(), "{}", formatted);
+        assert_eq!(formatted, run_fmt(&formatted).unwrap());
+    }
+}
+
+#[test]
+fn fmt_keeps_comments() {
+    let original = "// squares\nf(x) { x * x }\n{ n >= 0 }  // pre\nx := 0   /* start */\n{ x = 0 and n >= 0 }\nwhile x < n do\n    /* body */\n{ x < n and n >= 0 } ⊨ { x + 1 <= n }\n    x := x + 1 // step\n    { x <= n }\nend\n{ x = n }\n// the end\n";
+    let expected = "// squares\nf(x) {\n    x * x\n}\n\n{ n >= 0 }  // pre\nx := 0   /* start */\n{ x = 0 and n >= 0 }\nwhile x < n do\n    /* body */\n    { x < n and n >= 0 }\n    ⊨\n    { x + 1 <= n }\n    x := x + 1 // step\n    { x <= n }\nend\n{ x = n }\n// the end\n";
+
+    let formatted = run_fmt(original).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(formatted, run_fmt(&formatted).unwrap());
+
+    assert_eq!(run_fmt("x := 1; // one\n\n// two\ny := 2").unwrap(), "x := 1; // one\n// two\ny := 2\n");
+}
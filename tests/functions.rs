@@ -0,0 +1,87 @@
+use imp::error::ImpErrorInner;
+use imp::state::Configuration;
+use imp::{run_ax_partial, run_big};
+
+fn with_outline(funcdefs: &str) -> String {
+    format!("{}\n{{ x >= 0 }}\nskip\n{{ x >= 0 }}\n", funcdefs)
+}
+
+fn errors(src: &str) -> Vec<String> {
+    match run_ax_partial(src) {
+        Ok(()) => vec![],
+        Err(errs) => errs.0.iter().map(|err| match err {
+            ImpErrorInner::Other(msg) => msg.clone(),
+            err => panic!("unexpected error {:?}", err),
+        }).collect(),
+    }
+}
+
+#[test]
+fn terminating_functions_are_admitted() {
+    assert!(errors(&with_outline("sum(n) { (n <= 0) ? 0 : n + sum(n - 1) }")).is_empty());
+    assert!(errors(&with_outline("down(m, n) decreases n - m { (m >= n) ? 0 : down(m + 1, n) }")).is_empty());
+}
+
+#[test]
+fn nonterminating_functions_are_rejected() {
+    // Assuming f(n) = f(n) + 1 would prove false
+    let inconsistent = "f(n) { f(n) + 1 }\n{ x >= 0 }\n⊨\n{ 0 = 1 }\nskip\n{ 0 = 1 }\n";
+    let errs = errors(inconsistent);
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("Could not prove that f terminates"), "{}", errs[0]);
+
+    let errs = errors(&with_outline("up(n) decreases n { (n >= 10) ? 0 : up(n + 1) }"));
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("the measure n must be non-negative and decrease"), "{}", errs[0]);
+}
+
+#[test]
+fn clauses_must_be_exhaustive_and_disjoint() {
+    assert!(errors(&with_outline("sign(n)\n    | n < 0 = -1\n    | n = 0 = 0\n    | n > 0 = 1")).is_empty());
+
+    let errs = errors(&with_outline("sign(n)\n    | n <= 0 = -1\n    | n >= 0 = 1"));
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("overlap"), "{}", errs[0]);
+
+    let errs = errors(&with_outline("sign(n)\n    | n < 0 = -1\n    | n > 0 = 1"));
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("not exhaustive"), "{}", errs[0]);
+}
+
+#[test]
+fn clauses_are_evaluated() {
+    let src = "fib(n)\n    | n <= 1 = n\n    | otherwise = fib(n - 1) + fib(n - 2)\n{ true }\nx := fib(10)\n{ true }\n";
+    match run_big(src).unwrap() {
+        Configuration::Terminal(state) => assert_eq!(state.get(&"x".to_owned()), 55),
+        config => panic!("ended in {:?}", config),
+    }
+
+    // The interpreters do not check that the clauses are exhaustive
+    let src = "pos(n)\n    | n > 0 = 1\n{ true }\nx := pos(0)\n{ true }\n";
+    assert!(matches!(
+        run_big(src),
+        Err(errs) if matches!(errs.0.as_slice(), [ImpErrorInner::RuntimeError { .. }])
+    ));
+}
+
+#[test]
+fn quantified_recursive_calls() {
+    // The bound n shadows the parameter n, so this would define p(x) = not p(x)
+    let errs = errors(&with_outline("p(n) := not (forall n. n > 0 -> p(n - 1))"));
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("Could not prove that p terminates"), "{}", errs[0]);
+
+    assert!(errors(&with_outline("q(n) := n <= 0 or (forall k. 0 <= k and k < n -> q(k))")).is_empty());
+}
+
+#[test]
+fn calls_must_match_the_definitions() {
+    let call = |assertion: &str| format!("even(n) := n % 2 = 0\nsq(n) {{ n * n }}\n{{ {} }}\nskip\n{{ {} }}\n", assertion, assertion);
+
+    assert!(errors(&call("even(x) and sq(x) >= 0")).is_empty());
+    assert_eq!(errors(&call("even(x) + 1 = 1")), vec!["The predicate even is called as an integer function"]);
+    assert_eq!(errors(&call("sq(x)")), vec!["The integer function sq is called as a predicate"]);
+    assert_eq!(errors(&call("sq(x, x) = 0")), vec!["sq takes 1 arguments, but is called with 2"]);
+    assert_eq!(errors(&call("odd(x)")), vec!["Call of the undefined function odd"]);
+    assert_eq!(errors(&with_outline("f(n) { g(n) }")), vec!["Call of the undefined function g"]);
+}
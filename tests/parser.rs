@@ -0,0 +1,55 @@
+use imp::ast::*;
+use imp::imp_lang::{AexpParser, AxProgramParser, BexpParser, StmParser};
+
+fn call(name: &str, args: Vec<Aexp>) -> Aexp {
+    Aexp::FuncApp(name.to_owned(), args)
+}
+
+#[test]
+fn call_in_parentheses_is_arithmetic() {
+    let stm = StmParser::new().parse("x := (f(2)) * 2").unwrap();
+    assert_eq!(*stm, Stm::Assign("x".to_owned(), Box::new(Aexp::Op(
+        Box::new(call("f", vec![Aexp::Numeral(2)])), Opcode::Mul, Box::new(Aexp::Numeral(2))
+    ))));
+
+    assert_eq!(*AexpParser::new().parse("((f(x)))").unwrap(), call("f", vec![Aexp::Var("x".to_owned())]));
+    assert_eq!(*AexpParser::new().parse("g((f(x)))").unwrap(), call("g", vec![call("f", vec![Aexp::Var("x".to_owned())])]));
+}
+
+#[test]
+fn call_in_parentheses_is_predicate() {
+    let p = Bexp::PredApp("p".to_owned(), vec![Aexp::Var("x".to_owned())]);
+    assert_eq!(*BexpParser::new().parse("(p(x))").unwrap(), p);
+    assert_eq!(*BexpParser::new().parse("((p(x))) and true").unwrap(), Bexp::Bop(
+        Box::new(p.clone()), Bopcode::And, Box::new(*BexpParser::new().parse("true").unwrap())
+    ));
+    assert_eq!(*AexpParser::new().parse("(p(x)) ? 1 : 2").unwrap(), Aexp::Ite(
+        Box::new(p), Box::new(Aexp::Numeral(1)), Box::new(Aexp::Numeral(2))
+    ));
+}
+
+#[test]
+fn call_in_parentheses_is_compared() {
+    let bexp = BexpParser::new().parse("(f(x)) = 1").unwrap();
+    assert_eq!(*bexp, Bexp::Rop(
+        Box::new(call("f", vec![Aexp::Var("x".to_owned())])), Ropcode::Eq, Box::new(Aexp::Numeral(1))
+    ));
+}
+
+#[test]
+fn call_in_parentheses_in_guard() {
+    let (funcdefs, _, _) = AxProgramParser::new().parse(
+        "g(n) | (even(n)) = 0 | (f(n)) < 2 = 1 | f(n) = 2 = 2 | otherwise = 3 { true } skip { true }"
+    ).unwrap();
+    let n = || Aexp::Var("n".to_owned());
+    let guards: Vec<Option<Bexp>> = match &funcdefs[0].body {
+        ImpFuncBody::Clauses(clauses) => clauses.iter().map(|clause| clause.guard.clone()).collect(),
+        body => panic!("{:?} is not defined by clauses", body),
+    };
+    assert_eq!(guards, vec![
+        Some(Bexp::PredApp("even".to_owned(), vec![n()])),
+        Some(Bexp::Rop(Box::new(call("f", vec![n()])), Ropcode::Lt, Box::new(Aexp::Numeral(2)))),
+        Some(Bexp::Rop(Box::new(call("f", vec![n()])), Ropcode::Eq, Box::new(Aexp::Numeral(2)))),
+        None,
+    ]);
+}
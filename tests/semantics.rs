@@ -0,0 +1,78 @@
+use imp::error::ImpErrorInner;
+use imp::state::{Configuration, State};
+use imp::{run_big, run_small};
+
+fn final_state(src: &str) -> State {
+    let big = match run_big(src).unwrap() {
+        Configuration::Terminal(state) => state,
+        config => panic!("{} ended in {:?}", src, config),
+    };
+    let small = match run_small(src).unwrap() {
+        Configuration::Terminal(state) => state,
+        config => panic!("{} ended in {:?}", src, config),
+    };
+    assert_eq!(big.vars(), small.vars(), "{}", src);
+    big
+}
+
+fn is_runtime_error(src: &str) -> bool {
+    let is_error = |res: imp::Result<Configuration>| matches!(
+        res, Err(errs) if matches!(errs.0.as_slice(), [ImpErrorInner::RuntimeError { .. }])
+    );
+    is_error(run_big(src)) && is_error(run_small(src))
+}
+
+#[test]
+fn division_and_modulo_are_euclidean() {
+    let state = final_state("a := -7 / 2; b := -7 % 2; c := 7 / -2; d := 7 % -2; e := 2 ^ 10");
+    assert_eq!(state.get(&"a".to_owned()), -4);
+    assert_eq!(state.get(&"b".to_owned()), 1);
+    assert_eq!(state.get(&"c".to_owned()), -3);
+    assert_eq!(state.get(&"d".to_owned()), 1);
+    assert_eq!(state.get(&"e".to_owned()), 1024);
+}
+
+#[test]
+fn arithmetic_errors_are_runtime_errors() {
+    assert!(is_runtime_error("x := 1 / 0"));
+    assert!(is_runtime_error("x := 1 % 0"));
+    assert!(is_runtime_error("x := 2 ^ (-1)"));
+    assert!(is_runtime_error("x := 2 ^ 63"));
+    assert!(is_runtime_error("x := 9223372036854775807 + 1"));
+}
+
+#[test]
+fn failed_assert_and_blocked_assume() {
+    assert!(matches!(run_big("x := 1; assert x = 2; x := 3").unwrap(), Configuration::Failed(..)));
+    assert!(matches!(run_small("x := 1; assert x = 2; x := 3").unwrap(), Configuration::Failed(..)));
+    assert!(matches!(run_big("x := 1; assume x = 2; x := 3").unwrap(), Configuration::Blocked(..)));
+    assert!(matches!(run_small("x := 1; assume x = 2; x := 3").unwrap(), Configuration::Blocked(..)));
+    assert_eq!(final_state("x := 1; assert x = 1; assume x < 2; x := 3").get(&"x".to_owned()), 3);
+}
+
+#[test]
+fn abort_is_stuck() {
+    assert!(matches!(run_big("x := 1; abort; x := 2").unwrap(), Configuration::Stuck(..)));
+    assert!(matches!(run_small("x := 1; abort; x := 2").unwrap(), Configuration::Stuck(..)));
+    assert!(matches!(run_big("if true then abort else skip end").unwrap(), Configuration::Stuck(..)));
+}
+
+#[test]
+fn repeat_and_for_loops() {
+    let state = final_state("x := 0; repeat x := x + 1 until x >= 3; s := 0; for i := 1 to 4 do s := s + i end");
+    assert_eq!(state.get(&"x".to_owned()), 3);
+    assert_eq!(state.get(&"s".to_owned()), 10);
+
+    // The body of repeat runs at least once, the body of for not at all for an empty range
+    let state = final_state("x := 5; repeat x := x + 1 until true; s := 0; for i := 1 to 0 do s := 1 end");
+    assert_eq!(state.get(&"x".to_owned()), 6);
+    assert_eq!(state.get(&"s".to_owned()), 0);
+}
+
+#[test]
+fn array_entries() {
+    let state = final_state("a[0] := 5; a[1] := a[0] + 1; i := 1; x := a[i] + a[2]");
+    assert_eq!(state.get_array(&"a".to_owned(), 0), 5);
+    assert_eq!(state.get_array(&"a".to_owned(), 1), 6);
+    assert_eq!(state.get(&"x".to_owned()), 6);
+}
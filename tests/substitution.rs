@@ -0,0 +1,32 @@
+use imp::ast::*;
+use imp::imp_lang::{AexpParser, BexpParser};
+
+fn bexp(src: &str) -> Bexp {
+    *BexpParser::new().parse(src).unwrap()
+}
+
+fn aexp(src: &str) -> Aexp {
+    *AexpParser::new().parse(src).unwrap()
+}
+
+#[test]
+fn array_assignment_substitutes_stores() {
+    let new_arr = Array::Store(Box::new(Array::Var("a".to_owned())), Box::new(aexp("0")), Box::new(aexp("x + 1")));
+    assert_eq!(bexp("a[i] = b[i] and a[0] > 0").substitute_array(&"a".to_owned(), &new_arr),
+               bexp("a[0 := x + 1][i] = b[i] and a[0 := x + 1][0] > 0"));
+
+    // Variables in indices are substituted as well
+    assert_eq!(bexp("a[i] = i").substitute(&"i".to_owned(), &aexp("i + 1")), bexp("a[i + 1] = i + 1"));
+}
+
+#[test]
+fn substitution_under_quantifiers() {
+    // Bound variables are not substituted
+    assert_eq!(bexp("forall k. a[k] <= k").substitute(&"k".to_owned(), &aexp("1")), bexp("forall k. a[k] <= k"));
+    assert_eq!(bexp("exists k. k < n").substitute(&"n".to_owned(), &aexp("n + 1")), bexp("exists k. k < n + 1"));
+
+    // The bound variable is renamed instead of capturing a free variable of the substituted expression
+    assert_eq!(bexp("forall k. k < n").substitute(&"n".to_owned(), &aexp("k + 1")), bexp("forall k1. k1 < k + 1"));
+    let new_arr = Array::Store(Box::new(Array::Var("a".to_owned())), Box::new(aexp("k")), Box::new(aexp("0")));
+    assert_eq!(bexp("forall k. a[k] <= 0").substitute_array(&"a".to_owned(), &new_arr), bexp("forall k1. a[k := 0][k1] <= 0"));
+}
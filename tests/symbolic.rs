@@ -0,0 +1,82 @@
+use imp::error::ImpErrorInner;
+use imp::imp_lang::{AxProgramParser, StmParser};
+use imp::symbolic::{explore, PathEnd, SymPath};
+use imp::{bmc, builtin_funcdefs, run_kinduction};
+use std::fs;
+
+fn paths(src: &str, max_iterations: usize) -> Vec<SymPath> {
+    let mut cfg = z3::Config::new();
+    cfg.set_timeout_msec(5000);
+    explore(&cfg, &StmParser::new().parse(src).unwrap(), &builtin_funcdefs(), max_iterations).unwrap()
+}
+
+#[test]
+fn infeasible_paths_are_pruned() {
+    // The second condition is decided by the first one
+    let paths = paths("if x > 0 then y := 1 else y := 2 end; if y = 1 then z := x else z := -x end", 5);
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|path| matches!(path.end, PathEnd::Terminal)));
+}
+
+#[test]
+fn loops_are_unrolled() {
+    let paths = paths("i := 0; while i < n do i := i + 1 end", 3);
+    // The loop runs 0, 1, 2 or 3 times, or more
+    assert_eq!(paths.len(), 5);
+    assert_eq!(paths.iter().filter(|path| matches!(path.end, PathEnd::Unfinished)).count(), 1);
+}
+
+#[test]
+fn failing_asserts_and_aborts_end_paths() {
+    let paths = paths("if x > 0 then abort else assert x < 0 end", 5);
+    assert_eq!(paths.len(), 3);
+    assert!(paths.iter().any(|path| matches!(path.end, PathEnd::Stuck)));
+    assert!(paths.iter().any(|path| matches!(path.end, PathEnd::Failed(_))));
+}
+
+fn check_bounded(src: &str, max_iterations: usize) -> imp::Result<bool> {
+    let mut cfg = z3::Config::new();
+    cfg.set_timeout_msec(5000);
+    let (_, _, prog) = AxProgramParser::new().parse(src).unwrap();
+    bmc::check_bounded(&cfg, &prog, &builtin_funcdefs(), max_iterations)
+}
+
+#[test]
+fn bounded_model_checking() {
+    // Complete, since the loop always runs 3 times
+    assert!(check_bounded("{ true }\ni := 0\n{ true }\nwhile i < 3 do\n    { true }\n    i := i + 1\n    { true }\nend\n{ i = 3 }", 3).unwrap());
+    assert!(!check_bounded("{ 0 <= n }\ni := 0\n{ true }\nwhile i < n do\n    { true }\n    i := i + 1\n    { true }\nend\n{ i = n }", 3).unwrap());
+
+    // The counterexample needs 4 iterations, so it is only found with a larger bound
+    let src = "{ 0 <= n }\ni := 0\n{ true }\nwhile i < n do\n    { true }\n    i := i + 1\n    { true }\nend\n{ i < 4 }";
+    assert!(!check_bounded(src, 3).unwrap());
+    match check_bounded(src, 4) {
+        Err(errs) => match errs.0.as_slice() {
+            [ImpErrorInner::AssertionViolation { initial, .. }] => assert_eq!(initial.get("n"), Some(&4)),
+            errs => panic!("unexpected errors {:?}", errs),
+        },
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn counterexamples_with_array_entries() {
+    // The counterexample has to set the array entry, which replaying it reads
+    match check_bounded("{ true }\nx := a[0]\n{ x = 0 }\n", 3) {
+        Err(errs) => match errs.0.as_slice() {
+            [ImpErrorInner::AssertionViolation { initial, state, .. }] => {
+                assert_ne!(initial.get("a[0]"), None);
+                assert_eq!(state.get("x"), initial.get("a[0]"));
+            },
+            errs => panic!("unexpected errors {:?}", errs),
+        },
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn k_induction() {
+    run_kinduction(&fs::read_to_string("examples/square.imp").unwrap()).unwrap();
+    run_kinduction("{ 0 <= n }\ni := 0; s := 0; while i < n do s := s + 2; i := i + 1 end\n{ s = 2 * n }").unwrap();
+    assert!(run_kinduction("{ 0 <= n }\ni := 0; s := 0; while i < n do s := s + 2; i := i + 1 end\n{ s = 2 * n + 1 }").is_err());
+}
@@ -0,0 +1,25 @@
+use imp::error::ImpErrorInner;
+use imp::{run_check, run_test};
+
+fn is_violation(res: imp::Result<()>) -> bool {
+    matches!(res, Err(errs) if matches!(errs.0.as_slice(), [ImpErrorInner::AssertionViolation { .. }]))
+}
+
+#[test]
+fn check_samples_variables_read_by_statements() {
+    // y only occurs in the statement, but has to be sampled as well
+    assert!(is_violation(run_check("{ true }\nx := y\n{ x = 0 }\n", None, false)));
+    run_check("{ true }\nx := y\n{ x = y }\n", None, false).unwrap();
+}
+
+#[test]
+fn test_samples_variables_read_by_statements() {
+    match run_test("{ true }\nx := y\n{ x = 0 }\n") {
+        Err(errs) => match errs.0.as_slice() {
+            // The counterexample is shrunk to the smallest value of y
+            [ImpErrorInner::AssertionViolation { initial, .. }] => assert_eq!(initial.get("y").map(|y| y.abs()), Some(1)),
+            errs => panic!("unexpected errors {:?}", errs),
+        },
+        Ok(()) => panic!("the postcondition x = 0 passed"),
+    }
+}